@@ -1,14 +1,19 @@
 //NOTE: ---------------------- NODE IMPLEMENTATION -----------------------------
 
 use ::std::clone::Clone;
-use std::{cmp::Ordering, collections::LinkedList};
+use std::{
+    cmp::Ordering,
+    collections::LinkedList,
+    fmt::{self, Display, Write},
+    io::IsTerminal,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum Color {
     Red,
     Black,
 }
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
 struct Node<K, V>
 where
     K: Ord + Clone + PartialEq + PartialOrd,
@@ -621,3 +626,234 @@ where
         return black == 0;
     }
 }
+
+//NOTE:***************************************************************************
+//   *  DEBUG AND TERMINAL PRETTY-PRINTING
+//   ***************************************************************************/
+impl<K: Ord + Clone + fmt::Debug, V: Ord + Clone + fmt::Debug> fmt::Debug for Node<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("key", &self.key)
+            .field("value", &self.value)
+            .field("color", &self.color)
+            .field("size", &self.size)
+            .field("left", &self.left.as_ref().map(|n| &n.key))
+            .field("right", &self.right.as_ref().map(|n| &n.key))
+            .finish()
+    }
+}
+
+impl<K: Ord + Clone + fmt::Debug, V: Ord + Clone + fmt::Debug> fmt::Debug for RedBlackTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries = Vec::new();
+        self.in_order(&self.root, &mut entries);
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PrettyLayout {
+    Sideways, // Root on the left, right subtree above, left subtree below
+    TopDown,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PrettyOptions {
+    pub layout: PrettyLayout,
+    pub color: bool, // ANSI red for red nodes; otherwise red nodes get an `R` tag
+    pub show_size: bool,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions {
+            layout: PrettyLayout::Sideways,
+            color: std::io::stdout().is_terminal(),
+            show_size: true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum PrettyEdge {
+    Root,
+    Up,
+    Down,
+}
+
+impl<K: Ord + Clone, V: Ord + Clone> RedBlackTree<K, V> {
+    fn in_order<'a>(&'a self, node: &'a Option<Box<Node<K, V>>>, out: &mut Vec<(&'a K, &'a V)>) {
+        if let Some(n) = node {
+            self.in_order(&n.left, out);
+            out.push((&n.key, &n.value));
+            self.in_order(&n.right, out);
+        }
+    }
+}
+
+impl<K: Ord + Clone + Display, V: Ord + Clone> RedBlackTree<K, V> {
+    pub fn pretty(&self) -> String {
+        self.pretty_with(&PrettyOptions::default())
+    }
+
+    pub fn pretty_with(&self, opts: &PrettyOptions) -> String {
+        let mut out = String::new();
+        if self.root.is_none() {
+            out.push_str("(empty)\n");
+            return out;
+        }
+        match opts.layout {
+            PrettyLayout::Sideways => {
+                self.pretty_sideways(&self.root, String::new(), PrettyEdge::Root, opts, &mut out)
+            }
+            PrettyLayout::TopDown => self.pretty_top_down(opts, &mut out),
+        }
+        out
+    }
+
+    // Returns the label as printed and its width in columns (without escape codes).
+    fn pretty_label(&self, n: &Node<K, V>, opts: &PrettyOptions) -> (String, usize) {
+        let mut plain = n.key.to_string();
+        if n.is_red() && !opts.color {
+            plain.push_str(" R");
+        }
+        if opts.show_size {
+            let _ = write!(plain, " ({})", n.size);
+        }
+        let width = plain.chars().count();
+        if n.is_red() && opts.color {
+            (format!("\x1b[31m{}\x1b[0m", plain), width)
+        } else {
+            (plain, width)
+        }
+    }
+
+    fn pretty_sideways(
+        &self,
+        node: &Option<Box<Node<K, V>>>,
+        prefix: String,
+        edge: PrettyEdge,
+        opts: &PrettyOptions,
+        out: &mut String,
+    ) {
+        if let Some(n) = node {
+            let above = if edge == PrettyEdge::Down {
+                "│   "
+            } else {
+                "    "
+            };
+            let below = if edge == PrettyEdge::Up {
+                "│   "
+            } else {
+                "    "
+            };
+            let (above, below) = if edge == PrettyEdge::Root {
+                ("", "")
+            } else {
+                (above, below)
+            };
+
+            self.pretty_sideways(&n.right, prefix.clone() + above, PrettyEdge::Up, opts, out);
+            let connector = match edge {
+                PrettyEdge::Root => "",
+                PrettyEdge::Up => "┌── ",
+                PrettyEdge::Down => "└── ",
+            };
+            let _ = writeln!(
+                out,
+                "{}{}{}",
+                prefix,
+                connector,
+                self.pretty_label(n, opts).0
+            );
+            self.pretty_sideways(&n.left, prefix + below, PrettyEdge::Down, opts, out);
+        }
+    }
+
+    fn pretty_top_down(&self, opts: &PrettyOptions, out: &mut String) {
+        // Every node gets its own column span in in-order position, so no two
+        // labels can overlap; rows[depth] collects (start, width, label, children).
+        let mut rows: Vec<Vec<PrettyCell>> = Vec::new();
+        let mut next_col = 0;
+        self.pretty_place(&self.root, 0, opts, &mut next_col, &mut rows);
+
+        for (depth, row) in rows.iter().enumerate() {
+            let mut line = String::new();
+            let mut col = 0;
+            for cell in row {
+                line.push_str(&" ".repeat(cell.start - col));
+                line.push_str(&cell.label);
+                col = cell.start + cell.width;
+            }
+            let _ = writeln!(out, "{}", line.trim_end());
+
+            if depth + 1 == rows.len() {
+                break;
+            }
+            let mut edges: Vec<char> = Vec::new();
+            for cell in row {
+                let center = cell.start + cell.width / 2;
+                let lo = cell.left_child.unwrap_or(center);
+                let hi = cell.right_child.unwrap_or(center);
+                if lo == hi {
+                    continue;
+                }
+                if edges.len() <= hi {
+                    edges.resize(hi + 1, ' ');
+                }
+                for c in edges.iter_mut().take(hi).skip(lo + 1) {
+                    *c = '─';
+                }
+                if cell.left_child.is_some() {
+                    edges[lo] = '┌';
+                }
+                if cell.right_child.is_some() {
+                    edges[hi] = '┐';
+                }
+                edges[center] = match (cell.left_child, cell.right_child) {
+                    (Some(_), Some(_)) => '┴',
+                    (Some(_), None) => '┘',
+                    _ => '└',
+                };
+            }
+            let _ = writeln!(out, "{}", edges.iter().collect::<String>().trim_end());
+        }
+    }
+
+    // Lays out the subtree and returns the center column of its root.
+    fn pretty_place(
+        &self,
+        node: &Option<Box<Node<K, V>>>,
+        depth: usize,
+        opts: &PrettyOptions,
+        next_col: &mut usize,
+        rows: &mut Vec<Vec<PrettyCell>>,
+    ) -> Option<usize> {
+        let n = node.as_ref()?;
+        let left_child = self.pretty_place(&n.left, depth + 1, opts, next_col, rows);
+        let (label, width) = self.pretty_label(n, opts);
+        let start = *next_col;
+        *next_col += width + 1;
+        let right_child = self.pretty_place(&n.right, depth + 1, opts, next_col, rows);
+
+        if rows.len() <= depth {
+            rows.resize_with(depth + 1, Vec::new);
+        }
+        rows[depth].push(PrettyCell {
+            start,
+            width,
+            label,
+            left_child,
+            right_child,
+        });
+        Some(start + width / 2)
+    }
+}
+
+struct PrettyCell {
+    start: usize,
+    width: usize,
+    label: String,
+    left_child: Option<usize>,
+    right_child: Option<usize>,
+}
@@ -0,0 +1,298 @@
+//NOTE: ---------------------- ANCHORS ------------------------------------------
+//
+// Anchors are char positions that follow edits. They live in two treaps, one
+// per bias, ordered by position. A node stores its distance (gap) from the
+// anchor before it rather than its position, so an edit only touches the gap
+// of the first anchor after it: inserting text is two splits and two merges,
+// and removing a range collapses every anchor inside it by tagging a subtree,
+// never by visiting each anchor. Both cost O(log m) for m anchors.
+//
+// The treaps are the crate's arena treap, whose parent links let an AnchorId
+// find its own node. An anchor goes away once every clone of its AnchorId has
+// been dropped.
+
+use crate::changeset::Bias;
+use crate::rope::Rope;
+use crate::treap::{Treap, NIL};
+use crate::RangeError;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Weak};
+
+const MIN_SWEEP: usize = 64;
+
+// A handle to an anchor. It is only meaningful for the rope that created it
+// and for clones of that rope.
+#[derive(Debug, Clone)]
+pub struct AnchorId(Arc<usize>); // Arena slot
+
+impl PartialEq for AnchorId {
+    fn eq(&self, other: &AnchorId) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for AnchorId {}
+
+impl Hash for AnchorId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+// A node's weight is its gap: chars from the previous anchor in the same tree.
+#[derive(Debug, Clone)]
+struct Anchor {
+    owner: Weak<usize>,
+    bias: Option<Bias>, // None while the slot is free
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct AnchorSet {
+    nodes: Treap<Anchor>,
+    roots: [usize; 2], // Indexed by `tree(bias)`
+    sweep_at: usize,   // Live slots that trigger a sweep of dropped anchors
+}
+
+impl Default for AnchorSet {
+    fn default() -> Self {
+        AnchorSet {
+            nodes: Treap::default(),
+            roots: [NIL, NIL],
+            sweep_at: MIN_SWEEP,
+        }
+    }
+}
+
+fn tree(bias: Bias) -> usize {
+    match bias {
+        Bias::Left => 0,
+        Bias::Right => 1,
+    }
+}
+
+impl AnchorSet {
+    fn is_empty(&self) -> bool {
+        self.roots == [NIL, NIL]
+    }
+
+    fn set_root(&mut self, bias: usize, t: usize) {
+        self.roots[bias] = self.nodes.detach(t);
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Following edits
+    //   ***************************************************************************
+    pub(crate) fn insert(&mut self, char_idx: usize, len: usize) {
+        if self.is_empty() || len == 0 {
+            return;
+        }
+        for bias in [Bias::Left, Bias::Right] {
+            let root = self.roots[tree(bias)];
+            let (before, after) = self.nodes.split_sum(root, char_idx, bias == Bias::Left, 0);
+            self.nodes.map_first_weight(after, |gap| gap + len);
+            let root = self.nodes.merge(before, after);
+            self.set_root(tree(bias), root);
+        }
+    }
+
+    // Anchors inside the removed range move to its start.
+    pub(crate) fn remove(&mut self, start: usize, end: usize) {
+        if self.is_empty() || start == end {
+            return;
+        }
+        for bias in [0, 1] {
+            let (before, rest) = self.nodes.split_sum(self.roots[bias], start, true, 0);
+            let before_end = self.nodes.sum(before);
+            let (inside, after) = self.nodes.split_sum(rest, end, true, before_end);
+            let inside_end = before_end + self.nodes.sum(inside);
+            let mut last = before_end;
+            if inside != NIL {
+                self.nodes.zero(inside);
+                self.nodes.map_first_weight(inside, |_| start - before_end);
+                last = start;
+            }
+            self.nodes
+                .map_first_weight(after, |gap| inside_end + gap - (end - start) - last);
+            let root = self.nodes.merge(before, inside);
+            let root = self.nodes.merge(root, after);
+            self.set_root(bias, root);
+        }
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Creating, finding and dropping anchors
+    //   ***************************************************************************
+    fn create(&mut self, char_idx: usize, bias: Bias) -> AnchorId {
+        if self.nodes.live() >= self.sweep_at {
+            self.sweep();
+        }
+        let (before, after) = self
+            .nodes
+            .split_sum(self.roots[tree(bias)], char_idx, true, 0);
+        let gap = char_idx - self.nodes.sum(before);
+        let slot = self.nodes.alloc(
+            gap,
+            Anchor {
+                owner: Weak::new(),
+                bias: Some(bias),
+            },
+        );
+        let id = AnchorId(Arc::new(slot));
+        self.nodes.value_mut(slot).owner = Arc::downgrade(&id.0);
+        self.nodes.map_first_weight(after, |next| next - gap);
+        let root = self.nodes.merge(before, slot);
+        let root = self.nodes.merge(root, after);
+        self.set_root(tree(bias), root);
+        id
+    }
+
+    fn slot(&self, id: &AnchorId) -> Option<usize> {
+        let slot = *id.0;
+        let anchor = &self.nodes.get(slot)?.value;
+        (anchor.bias.is_some() && std::ptr::eq(anchor.owner.as_ptr(), Arc::as_ptr(&id.0)))
+            .then_some(slot)
+    }
+
+    fn discard(&mut self, slot: usize) {
+        let rank = self.nodes.rank(slot);
+        let bias = tree(self.nodes.node(slot).value.bias.unwrap());
+        let (before, rest) = self.nodes.split_rank(self.roots[bias], rank);
+        let (node, after) = self.nodes.split_rank(rest, 1);
+        let gap = self.nodes.node(node).weight;
+        self.nodes.map_first_weight(after, |next| next + gap);
+        let root = self.nodes.merge(before, after);
+        self.set_root(bias, root);
+
+        *self.nodes.value_mut(slot) = Anchor {
+            owner: Weak::new(),
+            bias: None,
+        };
+        self.nodes.free(slot);
+    }
+
+    // Frees the anchors whose ids are gone. Runs when the live slots have
+    // doubled since the last sweep, so it is amortized O(log m) per anchor.
+    fn sweep(&mut self) {
+        for slot in 0..self.nodes.slots() {
+            let anchor = &self.nodes.node(slot).value;
+            if anchor.bias.is_some() && anchor.owner.strong_count() == 0 {
+                self.discard(slot);
+            }
+        }
+        self.sweep_at = MIN_SWEEP.max(2 * self.nodes.live());
+    }
+}
+
+impl Rope {
+    // An anchor at `char_idx`. Text inserted exactly there goes after a Left
+    // anchor and before a Right one; removing text around an anchor moves it
+    // to the start of the removed range.
+    pub fn create_anchor(&mut self, char_idx: usize, bias: Bias) -> AnchorId {
+        if char_idx > self.len_chars() {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: char_idx,
+                    len: self.len_chars()
+                }
+            );
+        }
+        self.anchors.create(char_idx, bias)
+    }
+
+    // None if the anchor belongs to another rope.
+    pub fn anchor_position(&self, id: &AnchorId) -> Option<usize> {
+        self.anchors
+            .slot(id)
+            .map(|slot| self.anchors.nodes.position(slot))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Rng;
+
+    #[test]
+    fn anchors_follow_random_edits() {
+        let mut rng = Rng(0x7137_449e_cd5c_8b3a);
+        let mut rope = Rope::from("x".repeat(200).as_str());
+        let mut anchors: Vec<(AnchorId, Bias, usize)> = Vec::new();
+        for _ in 0..2000 {
+            let len = rope.len_chars();
+            match rng.below(5) {
+                0 => {
+                    let at = rng.below(len + 1);
+                    let bias = [Bias::Left, Bias::Right][rng.below(2)];
+                    anchors.push((rope.create_anchor(at, bias), bias, at));
+                }
+                1 if !anchors.is_empty() => {
+                    anchors.swap_remove(rng.below(anchors.len()));
+                }
+                2 if len > 0 => {
+                    let start = rng.below(len);
+                    let end = start + rng.below((len - start).min(30) + 1);
+                    rope.remove(start..end);
+                    for (_, _, pos) in &mut anchors {
+                        if *pos >= end {
+                            *pos -= end - start;
+                        } else if *pos > start {
+                            *pos = start;
+                        }
+                    }
+                }
+                _ => {
+                    let at = rng.below(len + 1);
+                    let added = 1 + rng.below(20);
+                    rope.insert(at, &"y".repeat(added));
+                    for (_, bias, pos) in &mut anchors {
+                        if *pos > at || (*pos == at && *bias == Bias::Right) {
+                            *pos += added;
+                        }
+                    }
+                }
+            }
+            for (id, _, pos) in &anchors {
+                assert_eq!(rope.anchor_position(id), Some(*pos));
+            }
+        }
+    }
+
+    #[test]
+    fn bias_decides_which_side_of_an_insert() {
+        let mut rope = Rope::from("ab");
+        let left = rope.create_anchor(1, Bias::Left);
+        let right = rope.create_anchor(1, Bias::Right);
+        rope.insert(1, "123");
+        assert_eq!(rope.anchor_position(&left), Some(1));
+        assert_eq!(rope.anchor_position(&right), Some(4));
+        rope.remove(0..3);
+        assert_eq!(rope.anchor_position(&left), Some(0));
+        assert_eq!(rope.anchor_position(&right), Some(1));
+    }
+
+    #[test]
+    fn dropped_anchors_are_reclaimed() {
+        let mut rope = Rope::from("text");
+        let kept = rope.create_anchor(2, Bias::Left);
+        for i in 0..10_000 {
+            drop(rope.create_anchor(i % 5, Bias::Right));
+        }
+        assert!(rope.anchors.nodes.live() <= 2 * MIN_SWEEP);
+        assert_eq!(rope.anchor_position(&kept), Some(2));
+    }
+
+    #[test]
+    fn ids_belong_to_their_rope() {
+        let mut rope = Rope::from("abc");
+        let id = rope.create_anchor(3, Bias::Left);
+        let mut clone = rope.clone();
+        clone.insert(0, "zz");
+        assert_eq!(clone.anchor_position(&id), Some(5));
+        assert_eq!(rope.anchor_position(&id), Some(3));
+
+        let mut other = Rope::from("abc");
+        let _own = other.create_anchor(0, Bias::Left);
+        assert_eq!(other.anchor_position(&id), None);
+    }
+}
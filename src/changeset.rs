@@ -0,0 +1,488 @@
+//NOTE: ---------------------- CHANGE SETS --------------------------------------
+//
+// A ChangeSet describes an edit of a whole document as a run of operations
+// that walk it from start to end: keep (retain) some chars, insert text, or
+// delete some chars. Lengths are in chars. Because a change set covers the
+// entire document it can be inverted, composed with the next change set and
+// transformed against a concurrent one, which is what syncing clients need.
+
+use crate::rope::{char_to_byte_in, Rope};
+use crate::RangeError;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Retain(usize),
+    Insert(String),
+    Delete(usize),
+}
+
+impl Operation {
+    // Chars of the document this operation covers or adds.
+    fn len(&self) -> usize {
+        match self {
+            Operation::Retain(n) | Operation::Delete(n) => *n,
+            Operation::Insert(text) => text.chars().count(),
+        }
+    }
+
+    // Splits off the first `n` chars, leaving the rest in `self`.
+    fn take(&mut self, n: usize) -> Operation {
+        match self {
+            Operation::Retain(m) => {
+                *m -= n;
+                Operation::Retain(n)
+            }
+            Operation::Delete(m) => {
+                *m -= n;
+                Operation::Delete(n)
+            }
+            Operation::Insert(text) => {
+                let rest = text.split_off(char_to_byte_in(text, n));
+                Operation::Insert(std::mem::replace(text, rest))
+            }
+        }
+    }
+}
+
+// Which side a position sticks to when text is inserted exactly there. Left
+// stays before the new text, Right moves past it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Bias {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeError {
+    // The change set expected a document of `expected` chars.
+    LengthMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for ChangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeError::LengthMismatch { expected, actual } => write!(
+                f,
+                "change set expects a document of {} chars, got {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl Error for ChangeError {}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChangeSet {
+    ops: Vec<Operation>,
+    len_before: usize,
+    len_after: usize,
+}
+
+impl ChangeSet {
+    // An empty change set; build it up with `retain`, `insert` and `delete`.
+    pub fn new() -> Self {
+        ChangeSet::default()
+    }
+
+    // The change set that leaves a document of `len` chars alone.
+    pub fn identity(len: usize) -> Self {
+        let mut changes = ChangeSet::new();
+        changes.retain(len);
+        changes
+    }
+
+    pub fn ops(&self) -> &[Operation] {
+        &self.ops
+    }
+
+    pub fn len_before(&self) -> usize {
+        self.len_before
+    }
+
+    pub fn len_after(&self) -> usize {
+        self.len_after
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.ops.iter().all(|op| matches!(op, Operation::Retain(_)))
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Building
+    //   ***************************************************************************
+    // Adjacent operations of one kind are merged, and an insert next to a
+    // delete always comes first, so equal edits compare equal.
+    pub fn retain(&mut self, n: usize) -> &mut Self {
+        if n == 0 {
+            return self;
+        }
+        self.len_before += n;
+        self.len_after += n;
+        match self.ops.last_mut() {
+            Some(Operation::Retain(m)) => *m += n,
+            _ => self.ops.push(Operation::Retain(n)),
+        }
+        self
+    }
+
+    pub fn insert(&mut self, text: &str) -> &mut Self {
+        if text.is_empty() {
+            return self;
+        }
+        self.len_after += text.chars().count();
+        let at = match self.ops.last() {
+            Some(Operation::Delete(_)) => self.ops.len() - 1,
+            _ => self.ops.len(),
+        };
+        match at.checked_sub(1).map(|i| &mut self.ops[i]) {
+            Some(Operation::Insert(prev)) => prev.push_str(text),
+            _ => self.ops.insert(at, Operation::Insert(text.to_string())),
+        }
+        self
+    }
+
+    pub fn delete(&mut self, n: usize) -> &mut Self {
+        if n == 0 {
+            return self;
+        }
+        self.len_before += n;
+        match self.ops.last_mut() {
+            Some(Operation::Delete(m)) => *m += n,
+            _ => self.ops.push(Operation::Delete(n)),
+        }
+        self
+    }
+
+    fn push(&mut self, op: Operation) {
+        match op {
+            Operation::Retain(n) => self.retain(n),
+            Operation::Insert(text) => self.insert(&text),
+            Operation::Delete(n) => self.delete(n),
+        };
+    }
+
+    fn check_len(&self, len: usize) -> Result<(), ChangeError> {
+        if len != self.len_before {
+            return Err(ChangeError::LengthMismatch {
+                expected: self.len_before,
+                actual: len,
+            });
+        }
+        Ok(())
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Applying and inverting
+    //   ***************************************************************************
+    pub fn apply(&self, rope: &mut Rope) -> Result<(), ChangeError> {
+        self.check_len(rope.len_chars())?;
+        let mut pos = 0;
+        for op in &self.ops {
+            match op {
+                Operation::Retain(n) => pos += n,
+                Operation::Insert(text) => {
+                    rope.insert_raw(pos, text);
+                    pos += op.len();
+                }
+                Operation::Delete(n) => rope.remove(pos..pos + n),
+            }
+        }
+        Ok(())
+    }
+
+    // The change set that undoes this one. `original` is the document before
+    // this change set was applied; the deleted text comes from it.
+    pub fn invert(&self, original: &Rope) -> Result<ChangeSet, ChangeError> {
+        self.check_len(original.len_chars())?;
+        let mut inverse = ChangeSet::new();
+        let mut pos = 0;
+        for op in &self.ops {
+            match op {
+                Operation::Retain(n) => {
+                    inverse.retain(*n);
+                    pos += n;
+                }
+                Operation::Insert(_) => {
+                    inverse.delete(op.len());
+                }
+                Operation::Delete(n) => {
+                    inverse.insert(&original.slice(pos..pos + n).to_string());
+                    pos += n;
+                }
+            }
+        }
+        Ok(inverse)
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Composing and transforming
+    //   ***************************************************************************
+    // One change set with the effect of this one followed by `next`.
+    pub fn compose(&self, next: &ChangeSet) -> Result<ChangeSet, ChangeError> {
+        next.check_len(self.len_after)?;
+        let mut composed = ChangeSet::new();
+        let mut a = OpCursor::new(&self.ops);
+        let mut b = OpCursor::new(&next.ops);
+        loop {
+            match (a.peek(), b.peek()) {
+                (Some(Operation::Delete(_)), _) => composed.push(a.next()),
+                (_, Some(Operation::Insert(_))) => composed.push(b.next()),
+                (None, None) => return Ok(composed),
+                (Some(x), Some(y)) => {
+                    let n = x.len().min(y.len());
+                    match (a.take(n), b.take(n)) {
+                        (Operation::Retain(_), op @ Operation::Retain(_))
+                        | (Operation::Retain(_), op @ Operation::Delete(_))
+                        | (op @ Operation::Insert(_), Operation::Retain(_)) => composed.push(op),
+                        // Text inserted by this set and deleted by the next
+                        _ => {}
+                    }
+                }
+                // Lengths were checked, so both run out together
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // Rewrites this change set to apply after `other`, where both were made
+    // against the same document. `self.transform(other)` after `other` gives
+    // the same text as `other.transform(self)` after `self` when the two calls
+    // use opposite biases. When both insert at one position, Left puts this
+    // set's text first.
+    pub fn transform(&self, other: &ChangeSet, bias: Bias) -> Result<ChangeSet, ChangeError> {
+        other.check_len(self.len_before)?;
+        let mut transformed = ChangeSet::new();
+        let mut a = OpCursor::new(&self.ops);
+        let mut b = OpCursor::new(&other.ops);
+        loop {
+            match (a.peek(), b.peek()) {
+                (Some(Operation::Insert(_)), Some(Operation::Insert(_))) if bias == Bias::Right => {
+                    transformed.push(Operation::Retain(b.next().len()))
+                }
+                (Some(Operation::Insert(_)), _) => transformed.push(a.next()),
+                (_, Some(Operation::Insert(_))) => {
+                    transformed.push(Operation::Retain(b.next().len()))
+                }
+                (None, None) => return Ok(transformed),
+                (Some(x), Some(y)) => {
+                    let n = x.len().min(y.len());
+                    match (a.take(n), b.take(n)) {
+                        (op @ Operation::Retain(_), Operation::Retain(_))
+                        | (op @ Operation::Delete(_), Operation::Retain(_)) => transformed.push(op),
+                        // Deleted by `other` already
+                        _ => {}
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // Where char `pos` of the old document ends up. Positions inside deleted
+    // text move to where the deletion was.
+    pub fn map_position(&self, pos: usize, bias: Bias) -> usize {
+        if pos > self.len_before {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: pos,
+                    len: self.len_before
+                }
+            );
+        }
+        let (mut old, mut new) = (0, 0);
+        for op in &self.ops {
+            match op {
+                Operation::Retain(n) => {
+                    if old + n > pos {
+                        return new + pos - old;
+                    }
+                    old += n;
+                    new += n;
+                }
+                Operation::Delete(n) => {
+                    if old + n > pos {
+                        return new;
+                    }
+                    old += n;
+                }
+                Operation::Insert(_) => {
+                    if old == pos && bias == Bias::Left {
+                        return new;
+                    }
+                    new += op.len();
+                }
+            }
+        }
+        new
+    }
+}
+
+// Walks a list of operations, handing out whole operations or pieces of them.
+struct OpCursor<'a> {
+    rest: std::slice::Iter<'a, Operation>,
+    head: Option<Operation>,
+}
+
+impl<'a> OpCursor<'a> {
+    fn new(ops: &'a [Operation]) -> Self {
+        let mut rest = ops.iter();
+        let head = rest.next().cloned();
+        OpCursor { rest, head }
+    }
+
+    fn peek(&self) -> Option<&Operation> {
+        self.head.as_ref()
+    }
+
+    fn next(&mut self) -> Operation {
+        let op = self.head.take().expect("cursor is exhausted");
+        self.head = self.rest.next().cloned();
+        op
+    }
+
+    fn take(&mut self, n: usize) -> Operation {
+        let head = self.head.as_mut().expect("cursor is exhausted");
+        if n == head.len() {
+            return self.next();
+        }
+        head.take(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Rng;
+
+    fn random_text(rng: &mut Rng, max: usize) -> String {
+        (0..rng.below(max + 1))
+            .map(|_| ['a', 'b', '\u{e9}', '\n', '\u{1f980}'][rng.below(5)])
+            .collect()
+    }
+
+    // A random edit of a document of `len` chars.
+    fn random_changes(rng: &mut Rng, len: usize) -> ChangeSet {
+        let mut changes = ChangeSet::new();
+        let mut left = len;
+        while left > 0 {
+            let n = 1 + rng.below(left.min(8));
+            match rng.below(3) {
+                0 => changes.delete(n),
+                1 => changes.insert(&random_text(rng, 4)).retain(n),
+                _ => changes.retain(n),
+            };
+            left -= n;
+        }
+        if rng.below(2) == 0 {
+            changes.insert(&random_text(rng, 4));
+        }
+        changes
+    }
+
+    fn applied(changes: &ChangeSet, text: &str) -> String {
+        let mut rope = Rope::from(text);
+        changes.apply(&mut rope).unwrap();
+        rope.to_string()
+    }
+
+    #[test]
+    fn invert_undoes() {
+        let mut rng = Rng(0x1f83_d9ab_fb41_bd6b);
+        for _ in 0..300 {
+            let text = random_text(&mut rng, 40);
+            let changes = random_changes(&mut rng, text.chars().count());
+            let after = applied(&changes, &text);
+            assert_eq!(changes.len_after(), after.chars().count());
+            let inverse = changes.invert(&Rope::from(text.as_str())).unwrap();
+            assert_eq!(applied(&inverse, &after), text);
+        }
+    }
+
+    #[test]
+    fn compose_is_one_then_the_other() {
+        let mut rng = Rng(0x5be0_cd19_137e_2179);
+        for _ in 0..300 {
+            let text = random_text(&mut rng, 40);
+            let first = random_changes(&mut rng, text.chars().count());
+            let middle = applied(&first, &text);
+            let second = random_changes(&mut rng, middle.chars().count());
+            let both = first.compose(&second).unwrap();
+            assert_eq!(applied(&both, &text), applied(&second, &middle));
+            assert_eq!(both.len_before(), first.len_before());
+        }
+    }
+
+    #[test]
+    fn transform_converges() {
+        let mut rng = Rng(0xcbbb_9d5d_c105_9ed8);
+        for _ in 0..300 {
+            let text = random_text(&mut rng, 40);
+            let len = text.chars().count();
+            let (a, b) = (random_changes(&mut rng, len), random_changes(&mut rng, len));
+            let a_after_b = a.transform(&b, Bias::Left).unwrap();
+            let b_after_a = b.transform(&a, Bias::Right).unwrap();
+            assert_eq!(
+                applied(&a_after_b, &applied(&b, &text)),
+                applied(&b_after_a, &applied(&a, &text))
+            );
+        }
+    }
+
+    #[test]
+    fn ties_and_positions() {
+        let mut a = ChangeSet::new();
+        a.retain(1).insert("A").retain(2);
+        let mut b = ChangeSet::new();
+        b.retain(1).insert("B").retain(2);
+        let a_first = a.transform(&b, Bias::Left).unwrap();
+        assert_eq!(applied(&a_first, &applied(&b, "xyz")), "xAByz");
+        let b_second = b.transform(&a, Bias::Right).unwrap();
+        assert_eq!(applied(&b_second, &applied(&a, "xyz")), "xAByz");
+
+        let mut edit = ChangeSet::new();
+        edit.retain(1).insert("new").delete(2).retain(3);
+        assert_eq!(edit.map_position(0, Bias::Right), 0);
+        assert_eq!(edit.map_position(1, Bias::Left), 1);
+        assert_eq!(edit.map_position(1, Bias::Right), 4);
+        assert_eq!(edit.map_position(2, Bias::Left), 4); // Inside the deletion
+        assert_eq!(edit.map_position(3, Bias::Left), 4);
+        assert_eq!(edit.map_position(6, Bias::Left), 7);
+    }
+
+    #[test]
+    fn building_normalizes_and_lengths_are_checked() {
+        let mut one = ChangeSet::new();
+        one.retain(1).delete(1).insert("x").retain(1).retain(1);
+        let mut other = ChangeSet::new();
+        other.retain(1).insert("x").delete(1).retain(2);
+        assert_eq!(one, other);
+        assert_eq!(
+            one.ops(),
+            [
+                Operation::Retain(1),
+                Operation::Insert("x".to_string()),
+                Operation::Delete(1),
+                Operation::Retain(2)
+            ]
+        );
+        assert!(ChangeSet::identity(4).is_identity());
+        assert_eq!(
+            one.apply(&mut Rope::from("ab")),
+            Err(ChangeError::LengthMismatch {
+                expected: 4,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            one.compose(&ChangeSet::identity(9)),
+            Err(ChangeError::LengthMismatch {
+                expected: 9,
+                actual: 4
+            })
+        );
+    }
+}
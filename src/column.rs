@@ -0,0 +1,253 @@
+//NOTE: ---------------------- DISPLAY COLUMNS ----------------------------------
+//
+// Where chars land on screen. A tab runs to the next tab stop, East Asian
+// wide and fullwidth chars take two columns, and everything else takes one.
+// The wide ranges are the W and F entries of Unicode's EastAsianWidth.txt,
+// kept here as a sorted table so the crate needs no dependencies.
+
+use crate::rope::Rope;
+use crate::slice::RopeSlice;
+
+// Inclusive ranges of code points with East Asian Width W or F.
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x231A, 0x231B),
+    (0x2329, 0x232A),
+    (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0),
+    (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267F, 0x267F),
+    (0x2693, 0x2693),
+    (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4),
+    (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3),
+    (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA),
+    (0x26FD, 0x26FD),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x274E, 0x274E),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27B0, 0x27B0),
+    (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x2E80, 0x2E99),
+    (0x2E9B, 0x2EF3),
+    (0x2F00, 0x2FD5),
+    (0x2FF0, 0x2FFF),
+    (0x3000, 0x303E),
+    (0x3041, 0x3096),
+    (0x3099, 0x30FF),
+    (0x3105, 0x312F),
+    (0x3131, 0x318E),
+    (0x3190, 0x31E5),
+    (0x31EF, 0x321E),
+    (0x3220, 0x3247),
+    (0x3250, 0x4DBF),
+    (0x4E00, 0xA48C),
+    (0xA490, 0xA4C6),
+    (0xA960, 0xA97C),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE10, 0xFE19),
+    (0xFE30, 0xFE52),
+    (0xFE54, 0xFE66),
+    (0xFE68, 0xFE6B),
+    (0xFF01, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x16FE0, 0x16FE4),
+    (0x16FF0, 0x16FF1),
+    (0x17000, 0x187F7),
+    (0x18800, 0x18CD5),
+    (0x18D00, 0x18D08),
+    (0x1AFF0, 0x1AFF3),
+    (0x1AFF5, 0x1AFFB),
+    (0x1AFFD, 0x1AFFE),
+    (0x1B000, 0x1B122),
+    (0x1B132, 0x1B132),
+    (0x1B150, 0x1B152),
+    (0x1B155, 0x1B155),
+    (0x1B164, 0x1B167),
+    (0x1B170, 0x1B2FB),
+    (0x1F004, 0x1F004),
+    (0x1F0CF, 0x1F0CF),
+    (0x1F18E, 0x1F18E),
+    (0x1F191, 0x1F19A),
+    (0x1F200, 0x1F202),
+    (0x1F210, 0x1F23B),
+    (0x1F240, 0x1F248),
+    (0x1F250, 0x1F251),
+    (0x1F260, 0x1F265),
+    (0x1F300, 0x1F320),
+    (0x1F32D, 0x1F335),
+    (0x1F337, 0x1F37C),
+    (0x1F37E, 0x1F393),
+    (0x1F3A0, 0x1F3CA),
+    (0x1F3CF, 0x1F3D3),
+    (0x1F3E0, 0x1F3F0),
+    (0x1F3F4, 0x1F3F4),
+    (0x1F3F8, 0x1F43E),
+    (0x1F440, 0x1F440),
+    (0x1F442, 0x1F4FC),
+    (0x1F4FF, 0x1F53D),
+    (0x1F54B, 0x1F54E),
+    (0x1F550, 0x1F567),
+    (0x1F57A, 0x1F57A),
+    (0x1F595, 0x1F596),
+    (0x1F5A4, 0x1F5A4),
+    (0x1F5FB, 0x1F64F),
+    (0x1F680, 0x1F6C5),
+    (0x1F6CC, 0x1F6CC),
+    (0x1F6D0, 0x1F6D2),
+    (0x1F6D5, 0x1F6D7),
+    (0x1F6DC, 0x1F6DF),
+    (0x1F6EB, 0x1F6EC),
+    (0x1F6F4, 0x1F6FC),
+    (0x1F7E0, 0x1F7EB),
+    (0x1F7F0, 0x1F7F0),
+    (0x1F90C, 0x1F93A),
+    (0x1F93C, 0x1F945),
+    (0x1F947, 0x1F9FF),
+    (0x1FA70, 0x1FA7C),
+    (0x1FA80, 0x1FA89),
+    (0x1FA8F, 0x1FAC6),
+    (0x1FACE, 0x1FADC),
+    (0x1FADF, 0x1FAE9),
+    (0x1FAF0, 0x1FAF8),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+fn is_wide(c: char) -> bool {
+    let c = c as u32;
+    WIDE.binary_search_by(|&(start, end)| {
+        if end < c {
+            std::cmp::Ordering::Less
+        } else if start > c {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    })
+    .is_ok()
+}
+
+// Columns `c` takes when it starts at column `col`. A tab width of 0 is
+// taken as 1.
+pub(crate) fn char_columns(c: char, col: usize, tab_width: usize) -> usize {
+    if c == '\t' {
+        let tab_width = tab_width.max(1);
+        tab_width - col % tab_width
+    } else if is_wide(c) {
+        2
+    } else {
+        1
+    }
+}
+
+// Chars in `line` before its line break.
+pub(crate) fn line_text_len(line: RopeSlice<'_>) -> usize {
+    let mut tail = line.chars().rev();
+    let breaks = match tail.next() {
+        Some('\n') => 1 + (tail.next() == Some('\r')) as usize,
+        Some('\r') => 1,
+        _ => 0,
+    };
+    line.len_chars() - breaks
+}
+
+impl Rope {
+    // The display column of `char_idx` within its line.
+    pub fn char_to_column(&self, char_idx: usize, tab_width: usize) -> usize {
+        let line_start = self.line_to_char(self.char_to_line(char_idx));
+        self.slice(line_start..char_idx)
+            .chars()
+            .fold(0, |col, c| col + char_columns(c, col, tab_width))
+    }
+
+    // The char shown at column `col` of a line. A column inside a tab or a
+    // wide char maps to that char; one past the text maps to the end of the
+    // text, before the line break.
+    pub fn column_to_char(&self, line_idx: usize, col: usize, tab_width: usize) -> usize {
+        let line = self.line(line_idx);
+        let mut at = self.line_to_char(line_idx);
+        let mut x = 0;
+        for c in line.slice(..line_text_len(line)).chars() {
+            x += char_columns(c, x, tab_width);
+            if x > col {
+                break;
+            }
+            at += 1;
+        }
+        at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_table_is_sorted_and_disjoint() {
+        for pair in WIDE.windows(2) {
+            assert!(
+                pair[0].0 <= pair[0].1 && pair[0].1 < pair[1].0,
+                "{:x?}",
+                pair
+            );
+        }
+        assert!(is_wide('\u{65e5}'));
+        assert!(is_wide('\u{1f980}'));
+        assert!(is_wide('\u{ff21}')); // Fullwidth A
+        assert!(!is_wide('a'));
+        assert!(!is_wide('\u{e9}'));
+        assert!(!is_wide('\u{ff61}')); // Halfwidth
+    }
+
+    #[test]
+    fn tabs_run_to_the_next_stop() {
+        let rope = Rope::from("\ta\tbc\td\n");
+        let columns: Vec<usize> = (0..=7).map(|i| rope.char_to_column(i, 4)).collect();
+        assert_eq!(columns, [0, 4, 5, 8, 9, 10, 12, 13]);
+        assert_eq!(rope.char_to_column(2, 0), 2); // A tab width of 0 counts as 1
+        assert_eq!(rope.char_to_column(2, 8), 9);
+    }
+
+    #[test]
+    fn wide_chars_take_two_columns() {
+        let rope = Rope::from("x\n\u{65e5}a\u{1f980}\tz\r\n");
+        assert_eq!(rope.char_to_column(2, 4), 0);
+        assert_eq!(rope.char_to_column(3, 4), 2);
+        assert_eq!(rope.char_to_column(5, 4), 5);
+        assert_eq!(rope.char_to_column(6, 4), 8);
+        assert_eq!(rope.char_to_column(7, 4), 9);
+    }
+
+    #[test]
+    fn columns_map_back_to_chars() {
+        let rope = Rope::from("x\n\u{65e5}a\u{1f980}\tz\r\nlast");
+        let back: Vec<usize> = (0..=11).map(|col| rope.column_to_char(1, col, 4)).collect();
+        // Inside a wide char or a tab maps to that char; past the text stops
+        // before the CRLF
+        assert_eq!(back, [2, 2, 3, 4, 4, 5, 5, 5, 6, 7, 7, 7]);
+        for c in 2..=7 {
+            assert_eq!(rope.column_to_char(1, rope.char_to_column(c, 4), 4), c);
+        }
+        assert_eq!(rope.column_to_char(2, 99, 4), rope.len_chars());
+        assert_eq!(rope.column_to_char(0, 0, 4), 0);
+    }
+}
@@ -0,0 +1,311 @@
+//NOTE: ---------------------- LINE DIFFS ---------------------------------------
+//
+// Myers' O((N+M)D) diff over the lines of two ropes. Lines are borrowed as
+// RopeSlices, never copied: the shared prefix and suffix are stripped by
+// comparing slices, and the lines left in the middle are interned by hash so
+// the search compares integers. Ropes own their nodes, so there is no shared
+// structure to short-circuit on; equal lines are found by content.
+
+use crate::rope::Rope;
+use crate::slice::RopeSlice;
+use std::collections::HashMap;
+use std::ops::Range;
+
+// One run of changed lines, as 0-based line ranges. An empty `old` is a pure
+// insertion before that line, an empty `new` a pure deletion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    pub context: usize, // Unchanged lines shown around each change
+    pub old_name: String,
+    pub new_name: String,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            context: 3,
+            old_name: "a".to_string(),
+            new_name: "b".to_string(),
+        }
+    }
+}
+
+// The lines a diff works on: each keeps its line break, and the empty line
+// after a final break is not counted.
+pub(crate) fn text_lines(rope: &Rope) -> Vec<RopeSlice<'_>> {
+    let mut lines: Vec<RopeSlice<'_>> = rope.lines().collect();
+    if lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+pub fn diff_lines(a: &Rope, b: &Rope) -> Vec<Hunk> {
+    diff_slices(&text_lines(a), &text_lines(b))
+}
+
+fn diff_slices<'a>(old: &[RopeSlice<'a>], new: &[RopeSlice<'a>]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(x, y)| x == y).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (old, new) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut ids: HashMap<RopeSlice<'a>, usize> = HashMap::new();
+    let mut intern = |line: &RopeSlice<'a>| {
+        let next = ids.len();
+        *ids.entry(*line).or_insert(next)
+    };
+    let a: Vec<usize> = old.iter().map(&mut intern).collect();
+    let b: Vec<usize> = new.iter().map(&mut intern).collect();
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (x, y) in myers(&a, &b).into_iter().chain([(a.len(), b.len())]) {
+        if x > i || y > j {
+            hunks.push(Hunk {
+                old: prefix + i..prefix + x,
+                new: prefix + j..prefix + y,
+            });
+        }
+        (i, j) = (x + 1, y + 1);
+    }
+    hunks
+}
+
+// The matched pairs (index in `a`, index in `b`) of a shortest edit script,
+// in order. `trace[d]` keeps the furthest-reaching x of each diagonal before
+// round d, over diagonals -(d+1)..=d+1, so memory is O(D^2).
+fn myers(a: &[usize], b: &[usize]) -> Vec<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v[at(-d - 1)..=at(d + 1)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let get = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        (x, y) = (prev_x, prev_y);
+    }
+    matches.reverse();
+    matches
+}
+
+//NOTE:***************************************************************************
+//   *  Unified format
+//   ***************************************************************************
+// `diff -u` output turning `a` into `b`; empty when they are equal.
+pub fn unified_diff(a: &Rope, b: &Rope, options: &DiffOptions) -> String {
+    let (old, new) = (text_lines(a), text_lines(b));
+    let hunks = diff_slices(&old, &new);
+    let mut out = String::new();
+    if hunks.is_empty() {
+        return out;
+    }
+    out.push_str(&format!(
+        "--- {}\n+++ {}\n",
+        options.old_name, options.new_name
+    ));
+    let context = options.context;
+    let mut first = 0;
+    while first < hunks.len() {
+        // Changes whose context would touch are shown as one hunk
+        let mut last = first;
+        while last + 1 < hunks.len()
+            && hunks[last + 1].old.start - hunks[last].old.end <= 2 * context
+        {
+            last += 1;
+        }
+        let old_start = hunks[first].old.start.saturating_sub(context);
+        let old_end = (hunks[last].old.end + context).min(old.len());
+        let new_start = hunks[first].new.start - (hunks[first].old.start - old_start);
+        let new_end = hunks[last].new.end + (old_end - hunks[last].old.end);
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            header_range(old_start, old_end - old_start),
+            header_range(new_start, new_end - new_start)
+        ));
+        let mut pos = old_start;
+        for hunk in &hunks[first..=last] {
+            for line in &old[pos..hunk.old.start] {
+                push_line(&mut out, ' ', line);
+            }
+            for line in &old[hunk.old.clone()] {
+                push_line(&mut out, '-', line);
+            }
+            for line in &new[hunk.new.clone()] {
+                push_line(&mut out, '+', line);
+            }
+            pos = hunk.old.end;
+        }
+        for line in &old[pos..old_end] {
+            push_line(&mut out, ' ', line);
+        }
+        first = last + 1;
+    }
+    out
+}
+
+// 1-based start and length; an empty range names the line before it.
+fn header_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+fn push_line(out: &mut String, tag: char, line: &RopeSlice<'_>) {
+    out.push(tag);
+    for chunk in line.chunks() {
+        out.push_str(chunk);
+    }
+    if !matches!(line.chars().next_back(), Some('\n' | '\r')) {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Rng;
+
+    fn lcs(a: &[String], b: &[String]) -> usize {
+        let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                table[i][j] = if a[i] == b[j] {
+                    table[i + 1][j + 1] + 1
+                } else {
+                    table[i + 1][j].max(table[i][j + 1])
+                };
+            }
+        }
+        table[0][0]
+    }
+
+    fn lines_of(rope: &Rope) -> Vec<String> {
+        text_lines(rope).iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn hunks_are_a_shortest_edit_script() {
+        let mut rng = Rng(0x7137_449e_2f9b_cd65);
+        for _ in 0..200 {
+            let mut random = || -> String {
+                (0..rng.below(30))
+                    .map(|_| ["a\n", "b\n", "c\n", "d\n"][rng.below(4)])
+                    .collect()
+            };
+            let (a, b) = (Rope::from(random().as_str()), Rope::from(random().as_str()));
+            let (old, new) = (lines_of(&a), lines_of(&b));
+            let hunks = diff_lines(&a, &b);
+
+            // Swapping each hunk's old lines for its new ones gives `b`
+            let mut rebuilt = Vec::new();
+            let mut pos = 0;
+            let mut changed = 0;
+            for hunk in &hunks {
+                assert!(hunk.old.start >= pos && !(hunk.old.is_empty() && hunk.new.is_empty()));
+                rebuilt.extend_from_slice(&old[pos..hunk.old.start]);
+                rebuilt.extend_from_slice(&new[hunk.new.clone()]);
+                changed += hunk.old.len() + hunk.new.len();
+                pos = hunk.old.end;
+            }
+            rebuilt.extend_from_slice(&old[pos..]);
+            assert_eq!(rebuilt, new);
+            assert_eq!(changed, old.len() + new.len() - 2 * lcs(&old, &new));
+        }
+    }
+
+    #[test]
+    fn unified_output() {
+        let a = Rope::from("1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n");
+        let b = Rope::from("1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13");
+        let options = DiffOptions {
+            context: 2,
+            ..DiffOptions::default()
+        };
+        assert_eq!(
+            unified_diff(&a, &b, &options),
+            "--- a\n+++ b\n\
+             @@ -1,5 +1,5 @@\n 1\n 2\n-3\n+three\n 4\n 5\n\
+             @@ -11,2 +11,3 @@\n 11\n 12\n+13\n\\ No newline at end of file\n"
+        );
+        assert_eq!(unified_diff(&a, &a.clone(), &options), "");
+    }
+
+    #[test]
+    fn nearby_changes_share_a_hunk() {
+        let a = Rope::from("a\nb\nc\nd\ne\n");
+        let b = Rope::from("A\nb\nc\nd\nE\n");
+        assert_eq!(
+            diff_lines(&a, &b),
+            [
+                Hunk {
+                    old: 0..1,
+                    new: 0..1
+                },
+                Hunk {
+                    old: 4..5,
+                    new: 4..5
+                }
+            ]
+        );
+        let diff = unified_diff(&a, &b, &DiffOptions::default());
+        assert_eq!(diff.matches("@@ -").count(), 1);
+        assert!(diff.contains("@@ -1,5 +1,5 @@\n"));
+        assert_eq!(
+            unified_diff(&Rope::new(), &Rope::from("x\n"), &DiffOptions::default()),
+            "--- a\n+++ b\n@@ -0,0 +1 @@\n+x\n"
+        );
+    }
+}
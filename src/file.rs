@@ -0,0 +1,202 @@
+//NOTE: ---------------------- FILES --------------------------------------------
+//
+// Loading and saving whole buffers. A save never writes over the user's file
+// in place: the text goes to a temp file next to it, is synced to disk, and
+// only then renamed over the original, so a crash leaves either the old file
+// or the new one. Loading strips a UTF-8 BOM and reports it, together with
+// the line ending most lines use, so the same bytes can be written back.
+
+use crate::line_ending::LineEnding;
+use crate::rope::Rope;
+use crate::stream::ReadError;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FileFormat {
+    pub bom: bool,
+    pub line_ending: LineEnding, // What most lines end with; LF when none do
+}
+
+impl Rope {
+    // Loads a file. The BOM and dominant line ending it had become the rope's
+    // `file_format`, which `save_atomic` writes back.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Rope, ReadError> {
+        let mut file = File::open(path)?;
+        let mut head = Vec::with_capacity(BOM.len());
+        (&mut file).take(BOM.len() as u64).read_to_end(&mut head)?;
+        let bom = head == BOM;
+        let rest: &[u8] = if bom { &[] } else { &head };
+        let mut rope = Rope::from_reader(rest.chain(file)).map_err(|e| match e {
+            ReadError::InvalidUtf8 { offset } if bom => ReadError::InvalidUtf8 {
+                offset: offset + BOM.len(),
+            },
+            e => e,
+        })?;
+        let line_ending = rope.line_ending_stats().dominant().unwrap_or_default();
+        rope.format = FileFormat { bom, line_ending };
+        Ok(rope)
+    }
+
+    // What `load` found; the default for a rope that was not loaded.
+    pub fn file_format(&self) -> FileFormat {
+        self.format
+    }
+
+    pub fn set_file_format(&mut self, format: FileFormat) {
+        self.format = format;
+    }
+
+    // Writes the text as it is, with a BOM in front if the rope's format asks
+    // for one. `line_ending` is only a report of what the file used: line
+    // endings in the text are not rewritten, so a loaded file saves back byte
+    // for byte.
+    pub fn save_atomic<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let (temp_path, temp) = create_temp_beside(path)?;
+        let written = (|| {
+            let mut writer = BufWriter::new(&temp);
+            if self.format.bom {
+                writer.write_all(BOM)?;
+            }
+            self.write_to(&mut writer)?;
+            writer.flush()?;
+            drop(writer);
+            if let Ok(metadata) = fs::metadata(path) {
+                temp.set_permissions(metadata.permissions())?;
+            }
+            temp.sync_all()?;
+            fs::rename(&temp_path, path)
+        })();
+        if written.is_err() {
+            let _ = fs::remove_file(&temp_path);
+            return written;
+        }
+        sync_parent(path);
+        Ok(())
+    }
+}
+
+fn parent_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+// The temp file has to be in the target's directory: a rename is only atomic
+// within one file system.
+fn create_temp_beside(path: &Path) -> io::Result<(PathBuf, File)> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+        .to_string_lossy();
+    loop {
+        let temp_path = parent_of(path).join(format!(
+            ".{}.{}-{}.tmp",
+            name,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Makes the rename itself durable. Directories can't be opened for syncing on
+// every platform, so failures here are ignored.
+fn sync_parent(path: &Path) {
+    if let Ok(dir) = File::open(parent_of(path)) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rbtree-file-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn bom_and_crlf_round_trip() {
+        let path = temp_path("bom-crlf.txt");
+        let bytes = b"\xEF\xBB\xBFfirst\r\nsecond\r\n\xC3\xA9\r\n";
+        fs::write(&path, bytes).unwrap();
+
+        let mut rope = Rope::load(&path).unwrap();
+        assert_eq!(rope.to_string(), "first\r\nsecond\r\n\u{e9}\r\n");
+        assert_eq!(
+            rope.file_format(),
+            FileFormat {
+                bom: true,
+                line_ending: LineEnding::CrLf
+            }
+        );
+        rope.save_atomic(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+
+        rope.insert(0, "zero\r\n");
+        rope.save_atomic(&path).unwrap();
+        assert!(fs::read(&path)
+            .unwrap()
+            .starts_with(b"\xEF\xBB\xBFzero\r\n"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn set_file_format_drops_a_bom() {
+        let path = temp_path("drop-bom.txt");
+        fs::write(&path, b"\xEF\xBB\xBFtext\n").unwrap();
+        let mut rope = Rope::load(&path).unwrap();
+        rope.set_file_format(FileFormat::default());
+        rope.save_atomic(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"text\n");
+        assert_eq!(Rope::from("new").file_format(), FileFormat::default());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_replaces_and_leaves_no_temp_file() {
+        let path = temp_path("replace.txt");
+        fs::write(&path, "old\n").unwrap();
+        let rope = Rope::from("new\n");
+        rope.save_atomic(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        let leftovers = fs::read_dir(parent_of(&path))
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(".replace.txt.")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_utf8_offsets_count_the_bom() {
+        let path = temp_path("invalid.txt");
+        fs::write(&path, b"\xEF\xBB\xBFab\xFFc").unwrap();
+        match Rope::load(&path) {
+            Err(ReadError::InvalidUtf8 { offset }) => assert_eq!(offset, 5),
+            other => panic!("expected invalid UTF-8, got {:?}", other.map(|_| ())),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
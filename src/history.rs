@@ -0,0 +1,317 @@
+//NOTE: ---------------------- EDIT HISTORY -------------------------------------
+//
+// Undo/redo for a rope. Every insert and remove is recorded as a change that
+// knows how to invert itself, and changes are grouped into revisions. The
+// revisions form a tree: editing after an undo starts a new branch instead of
+// throwing the undone work away, and `goto` can walk to any revision.
+//
+// Rope nodes are uniquely owned, so a snapshot would be a full copy. Storing
+// the inverted edits instead keeps each revision as small as its text.
+
+use crate::rope::Rope;
+use std::ops::{Range, RangeBounds};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Insert { at: usize, text: String }, // Char index
+    Remove { at: usize, text: String },
+}
+
+impl Change {
+    pub fn apply(&self, rope: &mut Rope) {
+        match self {
+            Change::Insert { at, text } => rope.insert_raw(*at, text),
+            Change::Remove { at, text } => rope.remove(*at..*at + text.chars().count()),
+        }
+    }
+
+    pub fn invert(&self) -> Change {
+        match self {
+            Change::Insert { at, text } => Change::Remove {
+                at: *at,
+                text: text.clone(),
+            },
+            Change::Remove { at, text } => Change::Insert {
+                at: *at,
+                text: text.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Revision {
+    parent: usize,
+    children: Vec<usize>,
+    last_child: Option<usize>, // Where redo goes: the branch visited last
+    changes: Vec<Change>,      // Turn the parent into this revision
+}
+
+#[derive(Debug, Clone)]
+pub struct EditHistory {
+    rope: Rope,
+    revisions: Vec<Revision>, // 0 is the root: the text we started with
+    current: usize,
+    pending: Vec<Change>,
+    depth: usize, // Open `begin` calls
+}
+
+impl EditHistory {
+    pub fn new(rope: Rope) -> Self {
+        EditHistory {
+            rope,
+            revisions: vec![Revision {
+                parent: 0,
+                children: Vec::new(),
+                last_child: None,
+                changes: Vec::new(),
+            }],
+            current: 0,
+            pending: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    pub fn rope(&self) -> &Rope {
+        &self.rope
+    }
+
+    pub fn into_rope(self) -> Rope {
+        self.rope
+    }
+
+    pub fn current_revision(&self) -> usize {
+        self.current
+    }
+
+    pub fn parent(&self, revision: usize) -> Option<usize> {
+        (revision != 0).then(|| self.revisions[revision].parent)
+    }
+
+    pub fn children(&self, revision: usize) -> &[usize] {
+        &self.revisions[revision].children
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Editing
+    //   ***************************************************************************
+    // Outside a transaction each edit is a revision of its own.
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        if text.is_empty() {
+            // Still checks the index, like `Rope::insert`
+            self.rope.insert(char_idx, text);
+            return;
+        }
+        let text = self.rope.converted(text).into_owned();
+        self.record(Change::Insert { at: char_idx, text });
+    }
+
+    pub fn remove<R: RangeBounds<usize>>(&mut self, range: R) {
+        let Range { start, end } = self.rope.char_range(range);
+        if start == end {
+            return;
+        }
+        let text = self.rope.slice(start..end).to_string();
+        self.record(Change::Remove { at: start, text });
+    }
+
+    pub(crate) fn record(&mut self, change: Change) {
+        change.apply(&mut self.rope);
+        self.pending.push(change);
+        if self.depth == 0 {
+            self.finish();
+        }
+    }
+
+    // Opens a transaction. Transactions nest; the edits become one revision
+    // when the outermost one is committed.
+    pub fn begin(&mut self) {
+        self.depth += 1;
+    }
+
+    pub fn commit(&mut self) {
+        if self.depth == 0 {
+            panic!("commit without a matching begin");
+        }
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.finish();
+        }
+    }
+
+    // Turns the pending edits into a child of the current revision.
+    fn finish(&mut self) {
+        self.depth = 0;
+        if self.pending.is_empty() {
+            return;
+        }
+        let id = self.revisions.len();
+        self.revisions.push(Revision {
+            parent: self.current,
+            children: Vec::new(),
+            last_child: None,
+            changes: std::mem::take(&mut self.pending),
+        });
+        let parent = &mut self.revisions[self.current];
+        parent.children.push(id);
+        parent.last_child = Some(id);
+        self.current = id;
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Undo and redo
+    //   ***************************************************************************
+    // An open transaction is committed before undo, redo or goto.
+    pub fn undo(&mut self) -> bool {
+        self.finish();
+        if self.current == 0 {
+            return false;
+        }
+        let revision = &self.revisions[self.current];
+        for change in revision.changes.iter().rev() {
+            change.invert().apply(&mut self.rope);
+        }
+        let (child, parent) = (self.current, revision.parent);
+        self.revisions[parent].last_child = Some(child);
+        self.current = parent;
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.finish();
+        match self.revisions[self.current].last_child {
+            Some(child) => {
+                self.enter(child);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn enter(&mut self, child: usize) {
+        for change in &self.revisions[child].changes {
+            change.apply(&mut self.rope);
+        }
+        self.revisions[self.current].last_child = Some(child);
+        self.current = child;
+    }
+
+    // Moves to any revision, undoing up to the common ancestor and redoing
+    // down the target's branch.
+    pub fn goto(&mut self, revision: usize) {
+        if revision >= self.revisions.len() {
+            panic!("no revision {}", revision);
+        }
+        self.finish();
+        let mut down = vec![revision];
+        while let Some(parent) = self.parent(*down.last().unwrap()) {
+            down.push(parent);
+        }
+        while !down.contains(&self.current) {
+            self.undo();
+        }
+        while let Some(next) = down.pop() {
+            if next == self.current {
+                break;
+            }
+        }
+        while let Some(next) = down.pop() {
+            self.enter(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Rng;
+
+    #[test]
+    fn editing_after_undo_starts_a_branch() {
+        let mut history = EditHistory::new(Rope::from("abc"));
+        history.insert(3, "d"); // 1
+        history.remove(0..1); // 2
+        assert!(history.undo());
+        history.insert(0, "x"); // 3, a sibling of 2
+        assert_eq!(history.rope().to_string(), "xabcd");
+        assert_eq!(history.children(1), [2, 3]);
+        assert_eq!(history.parent(3), Some(1));
+        assert_eq!(history.parent(0), None);
+
+        history.goto(2);
+        assert_eq!(history.rope().to_string(), "bcd");
+        assert!(history.undo());
+        // Redo follows the branch visited last
+        assert!(history.redo());
+        assert_eq!(history.current_revision(), 2);
+        assert!(!history.redo());
+        history.goto(0);
+        assert_eq!(history.rope().to_string(), "abc");
+        assert!(!history.undo());
+    }
+
+    #[test]
+    fn transactions_nest_into_one_revision() {
+        let mut history = EditHistory::new(Rope::new());
+        history.begin();
+        history.insert(0, "hello");
+        history.begin();
+        history.insert(5, " world");
+        history.commit();
+        history.remove(0..1);
+        history.commit();
+        assert_eq!(history.current_revision(), 1);
+        assert_eq!(history.rope().to_string(), "ello world");
+        assert!(history.undo());
+        assert_eq!(history.rope().to_string(), "");
+
+        // An open transaction is committed by undo
+        history.begin();
+        history.insert(0, "x");
+        assert!(history.undo());
+        assert_eq!(history.rope().to_string(), "");
+        assert_eq!(history.children(0).len(), 2);
+    }
+
+    #[test]
+    fn goto_reaches_every_revision() {
+        let mut rng = Rng(0x9b05_688c_2b3e_6c1f);
+        let mut history = EditHistory::new(Rope::from("start"));
+        let mut texts = vec!["start".to_string()];
+        for _ in 0..300 {
+            match rng.below(6) {
+                0 => {
+                    history.undo();
+                }
+                1 => {
+                    history.goto(rng.below(texts.len()));
+                }
+                _ => {
+                    let len = history.rope().len_chars();
+                    if len > 0 && rng.below(2) == 0 {
+                        let start = rng.below(len);
+                        history.remove(start..start + 1 + rng.below(len - start));
+                    } else {
+                        history.insert(rng.below(len + 1), ["a", "\u{e9}", "\n"][rng.below(3)]);
+                    }
+                    assert_eq!(history.current_revision(), texts.len());
+                    texts.push(history.rope().to_string());
+                }
+            }
+            assert_eq!(
+                history.rope().to_string(),
+                texts[history.current_revision()]
+            );
+        }
+        for revision in (0..texts.len()).rev() {
+            history.goto(revision);
+            assert_eq!(history.rope().to_string(), texts[revision]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "commit without a matching begin")]
+    fn unmatched_commit_panics() {
+        EditHistory::new(Rope::new()).commit();
+    }
+}
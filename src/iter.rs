@@ -0,0 +1,526 @@
+//NOTE: ---------------------- ROPE ITERATORS ------------------------------------
+//
+// All iterators borrow the rope and walk its leaves in place. Each keeps two
+// cursors, one per end, so they are double-ended, and a remaining count, so
+// `size_hint` is exact. Starting at an index is one root-to-leaf descent.
+
+use crate::rope::{Rope, RopeNode, TextInfo};
+use crate::slice::RopeSlice;
+use crate::RangeError;
+use std::iter::Copied;
+use std::{slice, str};
+
+// Path from the root down to one leaf.
+#[derive(Clone)]
+struct LeafCursor<'a> {
+    path: Vec<&'a RopeNode>,
+}
+
+impl<'a> LeafCursor<'a> {
+    // Descends to the leaf holding unit `idx` under `metric`; returns the
+    // cursor, the weights of the text before that leaf and the number of
+    // leaves before it.
+    fn seek(
+        root: &'a RopeNode,
+        mut idx: usize,
+        metric: fn(&TextInfo) -> usize,
+    ) -> (LeafCursor<'a>, TextInfo, usize) {
+        let mut path = vec![root];
+        let mut before = TextInfo::default();
+        let mut leaves = 0;
+        let mut node = root;
+        while !node.is_leaf() {
+            let left = node.left.as_deref().unwrap();
+            if idx < metric(&left.info) {
+                node = left;
+            } else {
+                idx -= metric(&left.info);
+                before = before.combine(&left.info);
+                leaves += left.leaves;
+                node = node.right.as_deref().unwrap();
+            }
+            path.push(node);
+        }
+        (LeafCursor { path }, before, leaves)
+    }
+
+    fn leaf(&self) -> &'a str {
+        &self.path.last().unwrap().chunk
+    }
+
+    fn step_forward(&mut self) -> bool {
+        while let Some(child) = self.path.pop() {
+            let parent = match self.path.last() {
+                Some(p) => *p,
+                None => break,
+            };
+            if std::ptr::eq(parent.left.as_deref().unwrap(), child) {
+                let mut node = parent.right.as_deref().unwrap();
+                self.path.push(node);
+                while let Some(l) = node.left.as_deref() {
+                    node = l;
+                    self.path.push(node);
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    fn step_back(&mut self) -> bool {
+        while let Some(child) = self.path.pop() {
+            let parent = match self.path.last() {
+                Some(p) => *p,
+                None => break,
+            };
+            if std::ptr::eq(parent.right.as_deref().unwrap(), child) {
+                let mut node = parent.left.as_deref().unwrap();
+                self.path.push(node);
+                while let Some(r) = node.right.as_deref() {
+                    node = r;
+                    self.path.push(node);
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Chunks
+//   ***************************************************************************
+// Yields the leaves covering a byte range, with the first and last trimmed to
+// the range. Both ends of the range have to be char boundaries.
+#[derive(Clone)]
+pub struct Chunks<'a> {
+    front: Option<LeafCursor<'a>>,
+    back: Option<LeafCursor<'a>>,
+    front_skip: usize, // Bytes to drop from the front leaf
+    back_keep: usize,  // Bytes to keep of the back leaf
+    remaining: usize,
+}
+
+impl<'a> Chunks<'a> {
+    pub(crate) fn new(rope: &'a Rope, start_byte: usize, end_byte: usize) -> Chunks<'a> {
+        let root = match rope.root.as_deref() {
+            Some(root) if start_byte < end_byte => root,
+            _ => {
+                return Chunks {
+                    front: None,
+                    back: None,
+                    front_skip: 0,
+                    back_keep: 0,
+                    remaining: 0,
+                }
+            }
+        };
+        let bytes = |i: &TextInfo| i.bytes;
+        let (front, front_before, front_leaves) = LeafCursor::seek(root, start_byte, bytes);
+        let (back, back_before, back_leaves) = LeafCursor::seek(root, end_byte - 1, bytes);
+        Chunks {
+            front: Some(front),
+            back: Some(back),
+            front_skip: start_byte - front_before.bytes,
+            back_keep: end_byte - back_before.bytes,
+            remaining: back_leaves - front_leaves + 1,
+        }
+    }
+
+    // Byte offset, within the rope, at which the leaf holding `byte_idx` starts.
+    pub(crate) fn leaf_start(rope: &Rope, byte_idx: usize) -> usize {
+        match rope.root.as_deref() {
+            Some(root) if byte_idx < root.info.bytes => {
+                LeafCursor::seek(root, byte_idx, |i| i.bytes).1.bytes
+            }
+            _ => byte_idx,
+        }
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let leaf = self.front.as_ref()?.leaf();
+        let end = if self.remaining == 1 {
+            leaf.len().min(self.back_keep)
+        } else {
+            leaf.len()
+        };
+        let chunk = &leaf[self.front_skip..end];
+        self.front_skip = 0;
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.front.as_mut()?.step_forward();
+        }
+        Some(chunk)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Chunks<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let leaf = self.back.as_ref()?.leaf();
+        let start = if self.remaining == 1 {
+            self.front_skip
+        } else {
+            0
+        };
+        let chunk = &leaf[start..leaf.len().min(self.back_keep)];
+        self.back_keep = usize::MAX;
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.back.as_mut()?.step_back();
+        }
+        Some(chunk)
+    }
+}
+
+impl<'a> ExactSizeIterator for Chunks<'a> {}
+
+//NOTE:***************************************************************************
+//   *  Bytes and chars
+//   ***************************************************************************
+#[derive(Clone)]
+pub struct Bytes<'a> {
+    front: Copied<slice::Iter<'a, u8>>,
+    back: Copied<slice::Iter<'a, u8>>,
+    chunks: Chunks<'a>,
+    remaining: usize,
+}
+
+impl<'a> Bytes<'a> {
+    // `start_byte` may fall inside a char, so the chunks start at its leaf and
+    // the front of that leaf is skipped here.
+    pub(crate) fn new(rope: &'a Rope, start_byte: usize, end_byte: usize) -> Bytes<'a> {
+        let leaf_start = Chunks::leaf_start(rope, start_byte);
+        let mut chunks = Chunks::new(rope, leaf_start, end_byte);
+        let mut front = [].iter().copied();
+        if start_byte > leaf_start {
+            let first = chunks.next().unwrap_or("");
+            front = first.as_bytes()[start_byte - leaf_start..].iter().copied();
+        }
+        Bytes {
+            front,
+            back: [].iter().copied(),
+            chunks,
+            remaining: end_byte - start_byte,
+        }
+    }
+}
+
+impl<'a> Iterator for Bytes<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(b) = self.front.next() {
+                self.remaining -= 1;
+                return Some(b);
+            }
+            match self.chunks.next() {
+                Some(chunk) => self.front = chunk.as_bytes().iter().copied(),
+                None => {
+                    self.remaining -= 1;
+                    return self.back.next();
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Bytes<'a> {
+    fn next_back(&mut self) -> Option<u8> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(b) = self.back.next_back() {
+                self.remaining -= 1;
+                return Some(b);
+            }
+            match self.chunks.next_back() {
+                Some(chunk) => self.back = chunk.as_bytes().iter().copied(),
+                None => {
+                    self.remaining -= 1;
+                    return self.front.next_back();
+                }
+            }
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for Bytes<'a> {}
+
+#[derive(Clone)]
+pub struct Chars<'a> {
+    front: str::Chars<'a>,
+    back: str::Chars<'a>,
+    chunks: Chunks<'a>,
+    remaining: usize,
+}
+
+impl<'a> Chars<'a> {
+    pub(crate) fn new(rope: &'a Rope, start_char: usize, end_char: usize) -> Chars<'a> {
+        Chars {
+            front: "".chars(),
+            back: "".chars(),
+            chunks: Chunks::new(
+                rope,
+                rope.char_to_byte(start_char),
+                rope.char_to_byte(end_char),
+            ),
+            remaining: end_char - start_char,
+        }
+    }
+}
+
+impl<'a> Iterator for Chars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(c) = self.front.next() {
+                self.remaining -= 1;
+                return Some(c);
+            }
+            match self.chunks.next() {
+                Some(chunk) => self.front = chunk.chars(),
+                None => {
+                    self.remaining -= 1;
+                    return self.back.next();
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Chars<'a> {
+    fn next_back(&mut self) -> Option<char> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(c) = self.back.next_back() {
+                self.remaining -= 1;
+                return Some(c);
+            }
+            match self.chunks.next_back() {
+                Some(chunk) => self.back = chunk.chars(),
+                None => {
+                    self.remaining -= 1;
+                    return self.front.next_back();
+                }
+            }
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for Chars<'a> {}
+
+//NOTE:***************************************************************************
+//   *  Lines
+//   ***************************************************************************
+// Lines keep their line break and borrow from the rope.
+#[derive(Clone)]
+pub struct Lines<'a> {
+    slice: RopeSlice<'a>,
+    front: usize,
+    back: usize, // One past the last line still to yield
+}
+
+impl<'a> Lines<'a> {
+    pub(crate) fn new(slice: RopeSlice<'a>, line_idx: usize) -> Lines<'a> {
+        check_index(line_idx, slice.len_lines());
+        Lines {
+            slice,
+            front: line_idx,
+            back: slice.len_lines(),
+        }
+    }
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = RopeSlice<'a>;
+
+    fn next(&mut self) -> Option<RopeSlice<'a>> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        Some(self.slice.line(self.front - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.back - self.front, Some(self.back - self.front))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Lines<'a> {
+    fn next_back(&mut self) -> Option<RopeSlice<'a>> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.slice.line(self.back))
+    }
+}
+
+impl<'a> ExactSizeIterator for Lines<'a> {}
+
+//NOTE:***************************************************************************
+//   *  Rope entry points
+//   ***************************************************************************
+impl Rope {
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks::new(self, 0, self.len_bytes())
+    }
+
+    // Chunks starting with the one that holds `char_idx`, and the char index
+    // at which that first chunk begins.
+    pub fn chunks_at(&self, char_idx: usize) -> (Chunks<'_>, usize) {
+        check_index(char_idx, self.len_chars());
+        let start = Chunks::leaf_start(self, self.char_to_byte(char_idx));
+        (
+            Chunks::new(self, start, self.len_bytes()),
+            self.byte_to_char(start),
+        )
+    }
+
+    pub fn bytes(&self) -> Bytes<'_> {
+        self.bytes_at(0)
+    }
+
+    pub fn bytes_at(&self, byte_idx: usize) -> Bytes<'_> {
+        check_index(byte_idx, self.len_bytes());
+        Bytes::new(self, byte_idx, self.len_bytes())
+    }
+
+    pub fn chars(&self) -> Chars<'_> {
+        self.chars_at(0)
+    }
+
+    pub fn chars_at(&self, char_idx: usize) -> Chars<'_> {
+        check_index(char_idx, self.len_chars());
+        Chars::new(self, char_idx, self.len_chars())
+    }
+
+    pub fn lines(&self) -> Lines<'_> {
+        self.lines_at(0)
+    }
+
+    pub fn lines_at(&self, line_idx: usize) -> Lines<'_> {
+        Lines::new(self.slice(..), line_idx)
+    }
+}
+
+pub(crate) fn check_index(idx: usize, len: usize) {
+    if idx > len {
+        panic!("{}", RangeError::OutOfBounds { index: idx, len });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rope::tests::from_chunks;
+    use crate::tests::Rng;
+
+    #[test]
+    fn chunks_are_the_leaves() {
+        let rope = from_chunks(&["ab", "c\u{e9}", "d"]);
+        assert_eq!(rope.chunks().collect::<Vec<_>>(), ["ab", "c\u{e9}", "d"]);
+        assert_eq!(
+            rope.chunks().rev().collect::<Vec<_>>(),
+            ["d", "c\u{e9}", "ab"]
+        );
+        let (chunks, start) = rope.chunks_at(3);
+        assert_eq!(start, 2);
+        assert_eq!(chunks.collect::<Vec<_>>(), ["c\u{e9}", "d"]);
+        assert_eq!(Rope::new().chunks().count(), 0);
+    }
+
+    #[test]
+    fn both_ends_meet_in_the_middle() {
+        let text = "a\u{e9}\u{1f980}\r\nb\n\u{65e5}c";
+        let rope = from_chunks(&["a\u{e9}", "\u{1f980}\r", "\nb\n", "\u{65e5}c"]);
+        let mut chars = rope.chars();
+        let mut front = String::new();
+        let mut back = Vec::new();
+        while let Some(c) = chars.next() {
+            front.push(c);
+            assert_eq!(
+                chars.len(),
+                text.chars().count() - front.chars().count() - back.len()
+            );
+            if let Some(c) = chars.next_back() {
+                back.push(c);
+            }
+        }
+        back.reverse();
+        front.extend(back);
+        assert_eq!(front, text);
+
+        let mut bytes = rope.bytes();
+        assert_eq!(bytes.len(), text.len());
+        assert_eq!(bytes.next_back(), Some(b'c'));
+        assert_eq!(bytes.next(), Some(b'a'));
+        assert_eq!(
+            bytes.collect::<Vec<_>>(),
+            text.as_bytes()[1..text.len() - 1]
+        );
+    }
+
+    #[test]
+    fn iterators_start_anywhere() {
+        let mut rng = Rng(0xa54f_f53a_5f1d_36f1);
+        let pieces = ["xy", "\u{e9}", "\r\n", "\n", "\u{1f980}"];
+        let text: String = (0..2000).map(|_| pieces[rng.below(pieces.len())]).collect();
+        let rope = Rope::from(text.as_str());
+        for _ in 0..50 {
+            let c = rng.below(rope.len_chars() + 1);
+            let b = rope.char_to_byte(c);
+            assert!(rope.chars_at(c).eq(text[b..].chars()));
+            assert!(rope.chars_at(c).rev().eq(text[b..].chars().rev()));
+            assert!(rope.bytes_at(b).eq(text[b..].bytes()));
+            let line = rng.below(rope.len_lines());
+            let tail: Vec<String> = rope.lines_at(line).map(|l| l.to_string()).collect();
+            assert_eq!(tail.len(), rope.len_lines() - line);
+            assert_eq!(tail.concat(), text[rope.line_to_byte(line)..]);
+        }
+    }
+
+    #[test]
+    fn lines_keep_their_breaks() {
+        let rope = from_chunks(&["one\r", "\ntwo\rthree\n"]);
+        let lines: Vec<String> = rope.lines().map(|l| l.to_string()).collect();
+        assert_eq!(lines, ["one\r\n", "two\r", "three\n", ""]);
+        let back: Vec<String> = rope.lines().rev().map(|l| l.to_string()).collect();
+        assert_eq!(back, ["", "three\n", "two\r", "one\r\n"]);
+        assert_eq!(rope.lines().len(), 4);
+    }
+}
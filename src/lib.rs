@@ -1,5 +1,61 @@
+mod anchor;
+mod changeset;
+mod column;
+mod diff;
+mod file;
+mod history;
+mod iter;
+mod line_ending;
+mod lsp;
+mod observer;
+mod oplog;
+mod oracle;
+mod patch;
+mod redblack;
+mod rope;
+mod search;
+mod selection;
+mod slice;
+mod stream;
+mod svg;
+mod transform;
+mod treap;
+mod words;
+mod wrap;
+
+pub use anchor::AnchorId;
+pub use changeset::{Bias, ChangeError, ChangeSet, Operation};
+pub use diff::{diff_lines, unified_diff, DiffOptions, Hunk};
+pub use file::FileFormat;
+pub use history::{Change, EditHistory};
+pub use iter::{Bytes, Chars, Chunks, Lines};
+pub use line_ending::{LineEnding, LineEndingStats};
+pub use lsp::{EditError, LspRange, Position, TextEdit};
+pub use observer::{TreeEvent, TreeObserver};
+pub use oplog::{parse_log, replay, RecordingTree, ReplayError, TreeOp};
+pub use oracle::{CheckedRedBlackTree, Divergence};
+pub use patch::{
+    apply_patch, apply_patch_with, HunkOutcome, PatchError, PatchOptions, PatchReport,
+};
+pub use rope::{RangeError, Rope};
+pub use search::Matches;
+pub use selection::{Motion, Selection, SelectionRange};
+pub use slice::RopeSlice;
+pub use stream::{ReadError, RopeBuilder, RopeReader};
+pub use svg::SvgOptions;
+pub use words::{CharClass, WordClasses};
+pub use wrap::WrapMap;
+
+use redblack::RbNode;
+use rope::{char_to_byte_in, resolve_range};
+
 use std::clone::Clone;
-use std::{cmp::Ordering, collections::LinkedList};
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Write},
+    io::IsTerminal,
+    ops::RangeBounds,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum Color {
@@ -7,7 +63,7 @@ enum Color {
     Black,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
 struct Node<K, V>
 where
     K: Ord + Clone + PartialEq + PartialOrd,
@@ -21,6 +77,9 @@ where
     size: i32, // Size is the weight of the node (length of the string it represents)
 }
 
+// A child pointer; `None` is an empty subtree.
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
 impl<K: Ord + Clone, V: Clone> Node<K, V> {
     pub fn is_red(&self) -> bool {
         self.color.is_red()
@@ -30,6 +89,37 @@ impl<K: Ord + Clone, V: Clone> Node<K, V> {
     }
 }
 
+impl<K: Ord + Clone, V: Clone> RbNode for Node<K, V> {
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    fn left(&self) -> &Link<K, V> {
+        &self.left
+    }
+
+    fn right(&self) -> &Link<K, V> {
+        &self.right
+    }
+
+    fn left_mut(&mut self) -> &mut Link<K, V> {
+        &mut self.left
+    }
+
+    fn right_mut(&mut self) -> &mut Link<K, V> {
+        &mut self.right
+    }
+
+    fn update(&mut self) {
+        let size = |link: &Link<K, V>| link.as_ref().map_or(0, |n| n.size);
+        self.size = 1 + size(&self.left) + size(&self.right);
+    }
+}
+
 impl Color {
     fn is_red(&self) -> bool {
         *self == Color::Red
@@ -49,18 +139,19 @@ where
     V: Clone,
 {
     root: Option<Box<Node<K, V>>>,
+    observer: Option<Box<dyn TreeObserver<K>>>,
 }
 
 impl<K: Ord + Clone, V: Clone> RedBlackTree<K, V> {
     pub fn new() -> Self {
-        Self { root: None }
+        Self {
+            root: None,
+            observer: None,
+        }
     }
 
     fn is_red(&self, node: &Option<Box<Node<K, V>>>) -> bool {
-        match node {
-            Some(n) => n.color == Color::Red,
-            None => false,
-        }
+        redblack::is_red(node)
     }
 
     fn size(&self, node: &Option<Box<Node<K, V>>>) -> i32 {
@@ -79,14 +170,14 @@ impl<K: Ord + Clone, V: Clone> RedBlackTree<K, V> {
     }
 
     pub fn get(&self, key: &K) -> Option<&V> {
-        self.get_key(self.root.as_ref(), key)
+        self.get_key(self.root.as_deref(), key)
     }
 
-    fn get_key<'a>(&'a self, node: Option<&'a Box<Node<K, V>>>, key: &K) -> Option<&V> {
+    fn get_key<'a>(&'a self, node: Option<&'a Node<K, V>>, key: &K) -> Option<&'a V> {
         if let Some(n) = node {
             match key.cmp(&n.key) {
-                Ordering::Less => self.get_key(n.left.as_ref(), key),
-                Ordering::Greater => self.get_key(n.right.as_ref(), key),
+                Ordering::Less => self.get_key(n.left.as_deref(), key),
+                Ordering::Greater => self.get_key(n.right.as_deref(), key),
                 Ordering::Equal => Some(&n.value),
             }
         } else {
@@ -94,8 +185,32 @@ impl<K: Ord + Clone, V: Clone> RedBlackTree<K, V> {
         }
     }
 
+    pub fn contains(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn set_observer(&mut self, observer: Box<dyn TreeObserver<K>>) {
+        self.observer = Some(observer);
+    }
+
+    pub fn take_observer(&mut self) -> Option<Box<dyn TreeObserver<K>>> {
+        self.observer.take()
+    }
+
+    // The event is only built when someone is listening, so keys are not
+    // cloned on the hot path.
+    fn notify(&mut self, event: impl FnOnce() -> TreeEvent<K>) {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_event(event());
+        }
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Red Black Tree Insertion
+    //   ***************************************************************************
     pub fn insert(&mut self, key: K, value: V) {
-        self.root = self.insert_rec(&mut self.root, key, value);
+        let mut root = self.root.take();
+        self.root = self.insert_rec(&mut root, key, value);
         if let Some(ref mut root) = self.root {
             root.color = Color::Black;
         }
@@ -108,139 +223,1154 @@ impl<K: Ord + Clone, V: Clone> RedBlackTree<K, V> {
         value: V,
     ) -> Option<Box<Node<K, V>>> {
         if let Some(mut n) = node.take() {
+            self.notify(|| TreeEvent::Descend(n.key.clone()));
             match key.cmp(&n.key) {
                 Ordering::Less => n.left = self.insert_rec(&mut n.left, key, value),
                 Ordering::Equal => n.value = value,
                 Ordering::Greater => n.right = self.insert_rec(&mut n.right, key, value),
             }
-
-            if self.is_red(&n.right) && !self.is_red(&n.left) {
-                n = self.rotate_left(n);
-            }
-            if self.is_red(&n.left) && self.is_red(&n.left.as_ref()?.left) {
-                n = self.rotate_right(n);
-            }
-            if self.is_red(&n.left) && self.is_red(&n.right) {
-                self.flip_colors(&mut Some(n.clone()));
-            }
-
-            n.size = 1 + self.size(&n.left) + self.size(&n.right);
-            return Some(n);
+            Some(self.balance(n))
         } else {
-            return Some(Box::new(Node {
+            self.notify(|| TreeEvent::NodeCreated(key.clone()));
+            Some(Box::new(Node {
                 key,
                 value,
                 color: Color::Red,
                 size: 1,
                 left: None,
                 right: None,
-            }));
+            }))
         }
     }
 
-    fn rotate_left(&mut self, node: Option<Box<Node<K, V>>>) -> Option<Box<Node<K, V>>> {
-        if let Some(mut x) = node {
-            let mut y = x.right.take().unwrap();
-            x.right = y.left.take();
-            y.left = Some(x);
-            y.left.as_mut().unwrap().size = 1 + self.size(&y.left) + self.size(&y.right);
-            y.size = 1 + self.size(&y.left) + self.size(&y.right);
-            Some(y)
+    //NOTE:***************************************************************************
+    //   *  Red Black Tree Deletion
+    //   ***************************************************************************
+    // The root is made red when both its children are black, so the walk down
+    // always has a red link to push ahead of it.
+    pub fn delete(&mut self, key: &K) {
+        if !self.contains(key) {
+            return;
+        }
+        let mut root = self.root.take();
+        if let Some(ref mut r) = root {
+            if !self.is_red(&r.left) && !self.is_red(&r.right) {
+                r.color = Color::Red;
+            }
+        }
+        self.root = self.delete_node(root, key);
+        if let Some(ref mut root) = self.root {
+            root.color = Color::Black;
+        }
+    }
+
+    fn delete_node(&mut self, node: Option<Box<Node<K, V>>>, key: &K) -> Option<Box<Node<K, V>>> {
+        let mut n = node?;
+        self.notify(|| TreeEvent::Descend(n.key.clone()));
+        if key < &n.key {
+            if !self.is_red(&n.left) && !n.left.as_ref().is_some_and(|l| self.is_red(&l.left)) {
+                n = self.move_red_left(n);
+            }
+            n.left = self.delete_node(n.left.take(), key);
         } else {
-            None
+            if self.is_red(&n.left) {
+                n = self.rotate_right(Some(n))?;
+            }
+            if key == &n.key && n.right.is_none() {
+                self.notify(|| TreeEvent::NodeRemoved(key.clone()));
+                return None;
+            }
+            if !self.is_red(&n.right) && !n.right.as_ref().is_some_and(|r| self.is_red(&r.left)) {
+                n = self.move_red_right(n);
+            }
+            if key == &n.key {
+                // Put the successor in this node's place
+                self.notify(|| TreeEvent::NodeRemoved(key.clone()));
+                let (right, min) = self.delete_min_node(n.right.take()?);
+                n.right = right;
+                n.key = min.key;
+                n.value = min.value;
+            } else {
+                n.right = self.delete_node(n.right.take(), key);
+            }
         }
+        Some(self.balance(n))
+    }
+
+    pub fn delete_min(&mut self) {
+        let mut root = match self.root.take() {
+            Some(root) => root,
+            None => return,
+        };
+        if !self.is_red(&root.left) && !self.is_red(&root.right) {
+            root.color = Color::Red;
+        }
+        let (root, min) = self.delete_min_node(root);
+        self.root = root;
+        if let Some(ref mut root) = self.root {
+            root.color = Color::Black;
+        }
+        self.notify(|| TreeEvent::NodeRemoved(min.key.clone()));
+    }
+
+    // Unlinks the smallest node of the subtree and hands it back.
+    fn delete_min_node(&mut self, mut n: Box<Node<K, V>>) -> (Link<K, V>, Box<Node<K, V>>) {
+        self.notify(|| TreeEvent::Descend(n.key.clone()));
+        if n.left.is_none() {
+            return (None, n); // Left-leaning, so there is no right child either
+        }
+        if !self.is_red(&n.left) && !n.left.as_ref().is_some_and(|l| self.is_red(&l.left)) {
+            n = self.move_red_left(n);
+        }
+        let (left, min) = self.delete_min_node(n.left.take().unwrap());
+        n.left = left;
+        (Some(self.balance(n)), min)
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Standard BST HELPER FUNCTIONS
+    //   ***************************************************************************
+    // The child takes the parent's place and color; the parent goes down red.
+    fn rotate_left(&mut self, node: Option<Box<Node<K, V>>>) -> Option<Box<Node<K, V>>> {
+        let mut x = node?;
+        self.notify(|| TreeEvent::RotateLeft(x.key.clone()));
+        let color = std::mem::replace(&mut x.color, Color::Red);
+        let mut y = redblack::rotate_left(x);
+        y.color = color;
+        Some(y)
     }
 
     fn rotate_right(&mut self, node: Option<Box<Node<K, V>>>) -> Option<Box<Node<K, V>>> {
-        if let Some(mut x) = node {
-            let mut y = x.left.take().unwrap();
-            x.left = y.right.take();
-            y.right = Some(x);
-            y.right.as_mut().unwrap().size = 1 + self.size(&y.left) + self.size(&y.right);
-            y.size = 1 + self.size(&y.left) + self.size(&y.right);
-            Some(y)
-        } else {
-            None
+        let mut x = node?;
+        self.notify(|| TreeEvent::RotateRight(x.key.clone()));
+        let color = std::mem::replace(&mut x.color, Color::Red);
+        let mut y = redblack::rotate_right(x);
+        y.color = color;
+        Some(y)
+    }
+
+    fn flip_colors(&mut self, n: &mut Node<K, V>) {
+        self.notify(|| TreeEvent::FlipColors(n.key.clone()));
+        redblack::flip_colors(n);
+    }
+
+    // Makes the left child or one of its children red, borrowing from the
+    // right sibling when that one has a red link to spare.
+    fn move_red_left(&mut self, mut n: Box<Node<K, V>>) -> Box<Node<K, V>> {
+        self.notify(|| TreeEvent::MoveRedLeft(n.key.clone()));
+        self.flip_colors(&mut n);
+        if n.right.as_ref().is_some_and(|r| self.is_red(&r.left)) {
+            n.right = self.rotate_right(n.right.take());
+            n = self.rotate_left(Some(n)).unwrap();
+            self.flip_colors(&mut n);
+        }
+        n
+    }
+
+    fn move_red_right(&mut self, mut n: Box<Node<K, V>>) -> Box<Node<K, V>> {
+        self.notify(|| TreeEvent::MoveRedRight(n.key.clone()));
+        self.flip_colors(&mut n);
+        if n.left.as_ref().is_some_and(|l| self.is_red(&l.left)) {
+            n = self.rotate_right(Some(n)).unwrap();
+            self.flip_colors(&mut n);
+        }
+        n
+    }
+
+    // Restores the left-leaning invariants on the way back up.
+    fn balance(&mut self, mut n: Box<Node<K, V>>) -> Box<Node<K, V>> {
+        if self.is_red(&n.right) && !self.is_red(&n.left) {
+            n = self.rotate_left(Some(n)).unwrap();
+        }
+        if self.is_red(&n.left) && n.left.as_ref().is_some_and(|l| self.is_red(&l.left)) {
+            n = self.rotate_right(Some(n)).unwrap();
         }
+        if self.is_red(&n.left) && self.is_red(&n.right) {
+            self.flip_colors(&mut n);
+        }
+        n.size = 1 + self.size(&n.left) + self.size(&n.right);
+        n
     }
 
-    fn flip_colors(&mut self, node: &mut Option<Box<Node<K, V>>>) {
+    //NOTE:***************************************************************************
+    //   *  Ordered symbol table methods.
+    //   ***************************************************************************/
+    // The key with `rank` smaller keys before it.
+    pub fn select(&self, rank: i32) -> K {
+        if rank < 0 || rank >= self.tree_size() {
+            panic!("Select function ERROR: rank < 0 or rank > tree size")
+        }
+        self.select_node(rank, &self.root).unwrap().clone()
+    }
+
+    fn select_node<'a>(&'a self, rank: i32, node: &'a Option<Box<Node<K, V>>>) -> Option<&'a K> {
+        let n = node.as_ref()?;
+        let left_size = self.size(&n.left);
+        match left_size.cmp(&rank) {
+            Ordering::Less => self.select_node(rank - left_size - 1, &n.right),
+            Ordering::Equal => Some(&n.key),
+            Ordering::Greater => self.select_node(rank, &n.left),
+        }
+    }
+
+    // How many keys are smaller than `key`, whether or not it is in the tree.
+    pub fn rank(&self, key: &K) -> i32 {
+        let mut node = &self.root;
+        let mut rank = 0;
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                Ordering::Less => node = &n.left,
+                Ordering::Equal => return rank + self.size(&n.left),
+                Ordering::Greater => {
+                    rank += 1 + self.size(&n.left);
+                    node = &n.right;
+                }
+            }
+        }
+        rank
+    }
+
+    pub fn min(&self) -> K {
+        self.min_key().expect("Empty Tree").clone()
+    }
+
+    fn min_key(&self) -> Option<&K> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = node.left.as_ref() {
+            node = left;
+        }
+        Some(&node.key)
+    }
+
+    pub fn max(&self) -> K {
+        self.max_key().expect("Empty tree").clone()
+    }
+
+    fn max_key(&self) -> Option<&K> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = node.right.as_ref() {
+            node = right;
+        }
+        Some(&node.key)
+    }
+
+    // The largest key that is not greater than `key`; panics if there is none.
+    pub fn floor(&self, key: &K) -> K {
+        self.floor_entry(key).expect("no key at or below").0.clone()
+    }
+
+    fn floor_entry(&self, key: &K) -> Option<(&K, &V)> {
+        let mut node = self.root.as_ref();
+        let mut best = None;
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                Ordering::Less => node = n.left.as_ref(),
+                Ordering::Equal => return Some((&n.key, &n.value)),
+                Ordering::Greater => {
+                    best = Some((&n.key, &n.value));
+                    node = n.right.as_ref();
+                }
+            }
+        }
+        best
+    }
+
+    // The smallest key that is not less than `key`; panics if there is none.
+    pub fn ceiling(&self, key: &K) -> K {
+        self.ceiling_entry(key)
+            .expect("no key at or above")
+            .0
+            .clone()
+    }
+
+    fn ceiling_entry(&self, key: &K) -> Option<(&K, &V)> {
+        let mut node = self.root.as_ref();
+        let mut best = None;
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                Ordering::Greater => node = n.right.as_ref(),
+                Ordering::Equal => return Some((&n.key, &n.value)),
+                Ordering::Less => {
+                    best = Some((&n.key, &n.value));
+                    node = n.left.as_ref();
+                }
+            }
+        }
+        best
+    }
+
+    // All keys, in order.
+    pub fn keys(&self) -> impl Iterator<Item = K> {
+        let mut entries = Vec::new();
+        self.in_order(&self.root, &mut entries);
+        let keys: Vec<K> = entries.into_iter().map(|(k, _)| k.clone()).collect();
+        keys.into_iter()
+    }
+
+    // The keys from `low` to `high`, both included, in order.
+    pub fn keys_range(&self, low: K, high: K) -> impl Iterator<Item = K> {
+        let mut keys = Vec::new();
+        self.keys_queue(&self.root, &mut keys, &low, &high);
+        keys.into_iter()
+    }
+
+    fn keys_queue(&self, node: &Option<Box<Node<K, V>>>, keys: &mut Vec<K>, low: &K, high: &K) {
         if let Some(n) = node {
-            n.color = n.color.flip_color();
-            if let Some(left) = n.left.as_mut() {
-                left.color = left.color.flip_color();
+            if low < &n.key {
+                self.keys_queue(&n.left, keys, low, high);
+            }
+            if low <= &n.key && &n.key <= high {
+                keys.push(n.key.clone());
             }
-            if let Some(right) = n.right.as_mut() {
-                right.color = right.color.flip_color();
+            if high > &n.key {
+                self.keys_queue(&n.right, keys, low, high);
             }
         }
     }
 
-    // Rope characteristics: Concatenate two Ropes
-    pub fn concatenate(&mut self, other: &RedBlackTree<K, V>) {
-        let new_root = self.root.take();
-        self.root = Some(Box::new(Node {
-            key: self.min().unwrap(), // A temporary key, can be any representative key
-            value: "".to_string(),    // A placeholder value
-            left: new_root,
-            right: other.root.clone(), // Keep the other tree's root
-            color: Color::Black,       // New root must be black
-            size: self.tree_size() + other.tree_size(), // Update size
-        }));
+    // How many keys lie between `lo` and `hi`, both included.
+    pub fn size_size(&self, lo: &K, hi: &K) -> i32 {
+        if lo > hi {
+            return 0;
+        }
+        self.rank(hi) - self.rank(lo) + self.contains(hi) as i32
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Split and append
+    //   ***************************************************************************/
+    // Moves every entry with a key of at least `key` into a new tree. Both
+    // halves are rebuilt, in O(n log n).
+    pub fn split(&mut self, key: &K) -> RedBlackTree<K, V> {
+        let mut entries = Vec::with_capacity(self.tree_size() as usize);
+        Self::into_entries(self.root.take(), &mut entries);
+        let at = entries.partition_point(|(k, _)| k < key);
+        let mut upper = RedBlackTree::new();
+        for (k, v) in entries.drain(at..) {
+            upper.insert(k, v);
+        }
+        let mut lower = RedBlackTree::new();
+        for (k, v) in entries {
+            lower.insert(k, v);
+        }
+        self.root = lower.root.take();
+        upper
+    }
+
+    // Moves every entry of `other` into this tree, leaving `other` empty. A
+    // key in both trees takes the value from `other`.
+    pub fn append(&mut self, other: &mut RedBlackTree<K, V>) {
+        let mut entries = Vec::with_capacity(other.tree_size() as usize);
+        Self::into_entries(other.root.take(), &mut entries);
+        for (k, v) in entries {
+            self.insert(k, v);
+        }
+    }
+
+    fn into_entries(node: Option<Box<Node<K, V>>>, out: &mut Vec<(K, V)>) {
+        if let Some(n) = node {
+            let n = *n;
+            Self::into_entries(n.left, out);
+            out.push((n.key, n.value));
+            Self::into_entries(n.right, out);
+        }
+    }
+
+    //NOTE:***************************************************************************
+    //   *  CHECK INTEGRITY OF RED-BLACK TREE DATA STRUCTURE
+    //   ***************************************************************************/
+    pub fn check(&self) -> Result<(), &'static str> {
+        if !self.is_bst() {
+            return Err("not in symmetric order");
+        }
+        if !self.is_size_consistent() {
+            return Err("subtree counts not consistent");
+        }
+        if !self.is_rank_consistent() {
+            return Err("ranks not consistent");
+        }
+        if !self.is23() {
+            return Err("not a 2-3 tree");
+        }
+        if !self.is_balanced() {
+            return Err("not balanced");
+        }
+        Ok(())
+    }
+
+    fn is_bst(&self) -> bool {
+        self.is_bst_rec(&self.root, None, None)
+    }
+
+    fn is_bst_rec(&self, node: &Option<Box<Node<K, V>>>, min: Option<&K>, max: Option<&K>) -> bool {
+        if let Some(n) = node {
+            if min.is_some_and(|min| n.key <= *min) || max.is_some_and(|max| n.key >= *max) {
+                return false;
+            }
+            return self.is_bst_rec(&n.left, min, Some(&n.key))
+                && self.is_bst_rec(&n.right, Some(&n.key), max);
+        }
+        true
+    }
+
+    fn is_size_consistent(&self) -> bool {
+        self.is_size_consistent_rec(&self.root)
+    }
+
+    fn is_size_consistent_rec(&self, node: &Option<Box<Node<K, V>>>) -> bool {
+        if let Some(n) = node {
+            if n.size() != self.size(&n.left) + self.size(&n.right) + 1 {
+                return false;
+            }
+            return self.is_size_consistent_rec(&n.left) && self.is_size_consistent_rec(&n.right);
+        }
+        true
+    }
+
+    fn is_rank_consistent(&self) -> bool {
+        for i in 0..self.tree_size() {
+            if i != self.rank(&self.select(i)) {
+                return false;
+            }
+        }
+        for key in self.keys() {
+            if key != self.select(self.rank(&key)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // No red right links, and no node with red links on both sides of it.
+    fn is23(&self) -> bool {
+        self.is23_rec(&self.root, true)
+    }
+
+    fn is23_rec(&self, node: &Option<Box<Node<K, V>>>, is_root: bool) -> bool {
+        match node {
+            Some(n) => {
+                if self.is_red(&n.right) {
+                    return false;
+                }
+                if !is_root && n.is_red() && self.is_red(&n.left) {
+                    return false;
+                }
+                self.is23_rec(&n.left, false) && self.is23_rec(&n.right, false)
+            }
+            None => true,
+        }
+    }
+
+    // Every path from the root to a leaf has as many black links as the
+    // leftmost one.
+    fn is_balanced(&self) -> bool {
+        let mut black = 0;
+        let mut node = &self.root;
+        while let Some(n) = node {
+            if !n.is_red() {
+                black += 1;
+            }
+            node = &n.left;
+        }
+        self.is_balanced_rec(&self.root, black)
+    }
+
+    fn is_balanced_rec(&self, node: &Option<Box<Node<K, V>>>, mut black: i32) -> bool {
+        if let Some(n) = node {
+            if !n.is_red() {
+                black -= 1;
+            }
+            return self.is_balanced_rec(&n.left, black) && self.is_balanced_rec(&n.right, black);
+        }
+        black == 0
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for RedBlackTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The text of a tree is the in-order concatenation of its values. Indices are
+// in chars unless the method says bytes, and a char is never cut in half.
+impl<K: Ord + Clone, V: Clone + AsRef<str>> RedBlackTree<K, V> {
+    pub fn text_len(&self) -> usize {
+        let mut len = 0;
+        self.for_each_value(&self.root, &mut |text| {
+            len += text.chars().count();
+            true
+        });
+        len
     }
 
     // Substring retrieval
-    pub fn substring(&self, start: i32, end: i32) -> Option<String> {
-        if start < 0 || end > self.tree_size() || start > end {
-            return None; // Invalid range
+    pub fn substring(&self, start: usize, end: usize) -> Result<String, RangeError> {
+        if start > end {
+            return Err(RangeError::Reversed { start, end });
         }
         let mut result = String::new();
-        self.collect_substring(&self.root, &mut result, start, end);
-        Some(result)
+        let mut pos = 0;
+        self.collect_substring(&self.root, &mut result, start, end, &mut pos);
+        if pos < end {
+            return Err(RangeError::OutOfBounds {
+                index: end,
+                len: pos,
+            });
+        }
+        Ok(result)
+    }
+
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Result<String, RangeError> {
+        let range = resolve_range(range, self.text_len())?;
+        self.substring(range.start, range.end)
+    }
+
+    pub fn byte_substring(&self, start: usize, end: usize) -> Result<String, RangeError> {
+        if start > end {
+            return Err(RangeError::Reversed { start, end });
+        }
+        let start = self.byte_to_char(start)?;
+        let end = self.byte_to_char(end)?;
+        self.substring(start, end)
+    }
+
+    // Converts a byte offset into the text to a char index, failing when the
+    // offset lands inside a UTF-8 sequence.
+    pub fn byte_to_char(&self, byte_idx: usize) -> Result<usize, RangeError> {
+        let mut bytes = 0;
+        let mut chars = 0;
+        let mut found = None;
+        self.for_each_value(&self.root, &mut |text| {
+            if byte_idx <= bytes + text.len() {
+                let local = byte_idx - bytes;
+                found = Some(if text.is_char_boundary(local) {
+                    Ok(chars + text[..local].chars().count())
+                } else {
+                    Err(RangeError::NotCharBoundary { index: byte_idx })
+                });
+                return false;
+            }
+            bytes += text.len();
+            chars += text.chars().count();
+            true
+        });
+        match found {
+            Some(result) => result,
+            None if byte_idx == bytes => Ok(chars),
+            None => Err(RangeError::OutOfBounds {
+                index: byte_idx,
+                len: bytes,
+            }),
+        }
     }
 
     fn collect_substring(
         &self,
         node: &Option<Box<Node<K, V>>>,
         result: &mut String,
-        start: i32,
-        end: i32,
+        start: usize,
+        end: usize,
+        pos: &mut usize,
     ) {
         if let Some(n) = node {
-            if start < n.size {
-                self.collect_substring(&n.left, result, start, end);
+            self.collect_substring(&n.left, result, start, end, pos);
+            if *pos >= end {
+                return;
             }
 
-            if start < n.size && end > 0 {
-                let key_str = n.value.clone();
-                let len = key_str.len() as i32;
+            let text = n.value.as_ref();
+            let len = text.chars().count();
+            // Append the part of this node's string that falls inside the range
+            if *pos + len > start {
+                let from = char_to_byte_in(text, start.saturating_sub(*pos));
+                let to = char_to_byte_in(text, (end - *pos).min(len));
+                result.push_str(&text[from..to]);
+            }
+            *pos += len;
 
-                // Append valid range from the current node's string
-                if start < len && end > 0 {
-                    let start_idx = start.max(0);
-                    let end_idx = end.min(len);
-                    result.push_str(&key_str[start_idx as usize..end_idx as usize]);
-                }
+            self.collect_substring(&n.right, result, start, end, pos);
+        }
+    }
+
+    // In-order walk over the values; stops as soon as `f` returns false.
+    fn for_each_value(
+        &self,
+        node: &Option<Box<Node<K, V>>>,
+        f: &mut dyn FnMut(&str) -> bool,
+    ) -> bool {
+        match node {
+            Some(n) => {
+                self.for_each_value(&n.left, f)
+                    && f(n.value.as_ref())
+                    && self.for_each_value(&n.right, f)
+            }
+            None => true,
+        }
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  DEBUG AND TERMINAL PRETTY-PRINTING
+//   ***************************************************************************/
+impl<K: Ord + Clone + fmt::Debug, V: Clone + fmt::Debug> fmt::Debug for Node<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("key", &self.key)
+            .field("value", &self.value)
+            .field("color", &self.color)
+            .field("size", &self.size)
+            .field("left", &self.left.as_ref().map(|n| &n.key))
+            .field("right", &self.right.as_ref().map(|n| &n.key))
+            .finish()
+    }
+}
+
+impl<K: Ord + Clone + fmt::Debug, V: Clone + fmt::Debug> fmt::Debug for RedBlackTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries = Vec::new();
+        self.in_order(&self.root, &mut entries);
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PrettyLayout {
+    Sideways, // Root on the left, right subtree above, left subtree below
+    TopDown,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PrettyOptions {
+    pub layout: PrettyLayout,
+    pub color: bool, // ANSI red for red nodes; otherwise red nodes get an `R` tag
+    pub show_size: bool,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions {
+            layout: PrettyLayout::Sideways,
+            color: std::io::stdout().is_terminal(),
+            show_size: true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum PrettyEdge {
+    Root,
+    Up,
+    Down,
+}
+
+impl<K: Ord + Clone, V: Clone> RedBlackTree<K, V> {
+    fn in_order<'a>(&'a self, node: &'a Option<Box<Node<K, V>>>, out: &mut Vec<(&'a K, &'a V)>) {
+        if let Some(n) = node {
+            self.in_order(&n.left, out);
+            out.push((&n.key, &n.value));
+            self.in_order(&n.right, out);
+        }
+    }
+}
+
+impl<K: Ord + Clone + Display, V: Clone> RedBlackTree<K, V> {
+    pub fn pretty(&self) -> String {
+        self.pretty_with(&PrettyOptions::default())
+    }
+
+    pub fn pretty_with(&self, opts: &PrettyOptions) -> String {
+        let mut out = String::new();
+        if self.root.is_none() {
+            out.push_str("(empty)\n");
+            return out;
+        }
+        match opts.layout {
+            PrettyLayout::Sideways => {
+                self.pretty_sideways(&self.root, String::new(), PrettyEdge::Root, opts, &mut out)
+            }
+            PrettyLayout::TopDown => self.pretty_top_down(opts, &mut out),
+        }
+        out
+    }
+
+    // Returns the label as printed and its width in columns (without escape codes).
+    fn pretty_label(&self, n: &Node<K, V>, opts: &PrettyOptions) -> (String, usize) {
+        let mut plain = n.key.to_string();
+        if n.is_red() && !opts.color {
+            plain.push_str(" R");
+        }
+        if opts.show_size {
+            let _ = write!(plain, " ({})", n.size);
+        }
+        let width = plain.chars().count();
+        if n.is_red() && opts.color {
+            (format!("\x1b[31m{}\x1b[0m", plain), width)
+        } else {
+            (plain, width)
+        }
+    }
+
+    fn pretty_sideways(
+        &self,
+        node: &Option<Box<Node<K, V>>>,
+        prefix: String,
+        edge: PrettyEdge,
+        opts: &PrettyOptions,
+        out: &mut String,
+    ) {
+        if let Some(n) = node {
+            let above = if edge == PrettyEdge::Down {
+                "│   "
+            } else {
+                "    "
+            };
+            let below = if edge == PrettyEdge::Up {
+                "│   "
+            } else {
+                "    "
+            };
+            let (above, below) = if edge == PrettyEdge::Root {
+                ("", "")
+            } else {
+                (above, below)
+            };
+
+            self.pretty_sideways(&n.right, prefix.clone() + above, PrettyEdge::Up, opts, out);
+            let connector = match edge {
+                PrettyEdge::Root => "",
+                PrettyEdge::Up => "┌── ",
+                PrettyEdge::Down => "└── ",
+            };
+            let _ = writeln!(
+                out,
+                "{}{}{}",
+                prefix,
+                connector,
+                self.pretty_label(n, opts).0
+            );
+            self.pretty_sideways(&n.left, prefix + below, PrettyEdge::Down, opts, out);
+        }
+    }
+
+    fn pretty_top_down(&self, opts: &PrettyOptions, out: &mut String) {
+        // Every node gets its own column span in in-order position, so no two
+        // labels can overlap; rows[depth] collects (start, width, label, children).
+        let mut rows: Vec<Vec<PrettyCell>> = Vec::new();
+        let mut next_col = 0;
+        self.pretty_place(&self.root, 0, opts, &mut next_col, &mut rows);
+
+        for (depth, row) in rows.iter().enumerate() {
+            let mut line = String::new();
+            let mut col = 0;
+            for cell in row {
+                line.push_str(&" ".repeat(cell.start - col));
+                line.push_str(&cell.label);
+                col = cell.start + cell.width;
             }
+            let _ = writeln!(out, "{}", line.trim_end());
 
-            if end > n.size {
-                self.collect_substring(&n.right, result, start - n.size, end - n.size);
+            if depth + 1 == rows.len() {
+                break;
             }
+            let mut edges: Vec<char> = Vec::new();
+            for cell in row {
+                let center = cell.start + cell.width / 2;
+                let lo = cell.left_child.unwrap_or(center);
+                let hi = cell.right_child.unwrap_or(center);
+                if lo == hi {
+                    continue;
+                }
+                if edges.len() <= hi {
+                    edges.resize(hi + 1, ' ');
+                }
+                for c in edges.iter_mut().take(hi).skip(lo + 1) {
+                    *c = '─';
+                }
+                if cell.left_child.is_some() {
+                    edges[lo] = '┌';
+                }
+                if cell.right_child.is_some() {
+                    edges[hi] = '┐';
+                }
+                edges[center] = match (cell.left_child, cell.right_child) {
+                    (Some(_), Some(_)) => '┴',
+                    (Some(_), None) => '┘',
+                    _ => '└',
+                };
+            }
+            let _ = writeln!(out, "{}", edges.iter().collect::<String>().trim_end());
+        }
+    }
+
+    // Lays out the subtree and returns the center column of its root.
+    fn pretty_place(
+        &self,
+        node: &Option<Box<Node<K, V>>>,
+        depth: usize,
+        opts: &PrettyOptions,
+        next_col: &mut usize,
+        rows: &mut Vec<Vec<PrettyCell>>,
+    ) -> Option<usize> {
+        let n = node.as_ref()?;
+        let left_child = self.pretty_place(&n.left, depth + 1, opts, next_col, rows);
+        let (label, width) = self.pretty_label(n, opts);
+        let start = *next_col;
+        *next_col += width + 1;
+        let right_child = self.pretty_place(&n.right, depth + 1, opts, next_col, rows);
+
+        if rows.len() <= depth {
+            rows.resize_with(depth + 1, Vec::new);
         }
+        rows[depth].push(PrettyCell {
+            start,
+            width,
+            label,
+            left_child,
+            right_child,
+        });
+        Some(start + width / 2)
     }
+}
 
-    // Other Red-Black Tree methods (deletion, min, max, etc.) would remain here...
+struct PrettyCell {
+    start: usize,
+    width: usize,
+    label: String,
+    left_child: Option<usize>,
+    right_child: Option<usize>,
 }
 
-fn main() {
-    let mut rbt1 = RedBlackTree::new();
-    rbt1.insert("Hello", "World");
-    rbt1.insert("Goodbye", "World");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut rbt2 = RedBlackTree::new();
-    rbt2.insert("Good", "Morning");
+    // xorshift64, so the tests need no dependencies and are repeatable.
+    pub(crate) struct Rng(pub(crate) u64);
 
-    rbt1.concatenate(&rbt2);
-    let substring = rbt1.substring(0, 5).unwrap();
-    println!("Substring: {}", substring); // Output: Substring: Hello
+    impl Rng {
+        pub(crate) fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        pub(crate) fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    fn depth<K: Ord + Clone, V: Clone>(node: &Option<Box<Node<K, V>>>) -> usize {
+        match node {
+            Some(n) => 1 + depth(&n.left).max(depth(&n.right)),
+            None => 0,
+        }
+    }
+
+    // Checks order, sizes, left-leaning red links and black balance, and
+    // returns the black height.
+    fn check_node<K: Ord + Clone, V: Clone>(
+        node: &Option<Box<Node<K, V>>>,
+        parent_red: bool,
+        min: Option<&K>,
+        max: Option<&K>,
+    ) -> usize {
+        let n = match node {
+            Some(n) => n,
+            None => return 0,
+        };
+        assert!(min.is_none_or(|min| &n.key > min), "keys out of order");
+        assert!(max.is_none_or(|max| &n.key < max), "keys out of order");
+        let size = |c: &Option<Box<Node<K, V>>>| c.as_ref().map_or(0, |c| c.size);
+        assert_eq!(n.size, 1 + size(&n.left) + size(&n.right), "stale size");
+        assert!(
+            !n.right.as_ref().is_some_and(|r| r.is_red()),
+            "red right link"
+        );
+        assert!(!(parent_red && n.is_red()), "two red links in a row");
+        let left = check_node(&n.left, n.is_red(), min, Some(&n.key));
+        let right = check_node(&n.right, n.is_red(), Some(&n.key), max);
+        assert_eq!(left, right, "black heights differ");
+        left + !n.is_red() as usize
+    }
+
+    fn check<K: Ord + Clone, V: Clone>(tree: &RedBlackTree<K, V>) {
+        assert!(!tree.root.as_ref().is_some_and(|r| r.is_red()), "red root");
+        check_node(&tree.root, false, None, None);
+        let n = tree.tree_size() as f64;
+        assert!(depth(&tree.root) as f64 <= 2.0 * (n + 1.0).log2());
+    }
+
+    #[test]
+    fn sequential_inserts_stay_balanced() {
+        let mut tree = RedBlackTree::new();
+        for i in 0..4096 {
+            tree.insert(i, i);
+        }
+        check(&tree);
+        assert_eq!(tree.tree_size(), 4096);
+        assert!(depth(&tree.root) <= 24);
+        assert_eq!(tree.get(&4095), Some(&4095));
+    }
+
+    #[test]
+    fn random_inserts_keep_invariants() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let mut tree = RedBlackTree::new();
+        let mut keys = std::collections::BTreeMap::new();
+        for step in 0..2000 {
+            let key = rng.below(1000);
+            tree.insert(key, step);
+            keys.insert(key, step);
+            if step % 100 == 0 {
+                check(&tree);
+            }
+        }
+        check(&tree);
+        assert_eq!(tree.tree_size() as usize, keys.len());
+        for (key, value) in &keys {
+            assert_eq!(tree.get(key), Some(value));
+        }
+        assert_eq!(tree.get(&1000), None);
+    }
+
+    #[test]
+    fn floor_finds_the_entry_at_or_before() {
+        let mut tree = RedBlackTree::new();
+        for key in [10, 20, 30] {
+            tree.insert(key, key * 2);
+        }
+        assert_eq!(tree.floor_entry(&5), None);
+        assert_eq!(tree.floor_entry(&10), Some((&10, &20)));
+        assert_eq!(tree.floor_entry(&25), Some((&20, &40)));
+        assert_eq!(tree.floor(&99), 30);
+        assert_eq!(tree.ceiling_entry(&31), None);
+        assert_eq!(tree.ceiling(&11), 20);
+        assert_eq!((tree.min(), tree.max()), (10, 30));
+    }
+
+    fn pretty_sample() -> RedBlackTree<i32, char> {
+        let mut tree = RedBlackTree::new();
+        for (key, value) in [(1, 'a'), (2, 'b'), (3, 'c'), (4, 'd'), (5, 'e')] {
+            tree.insert(key, value);
+        }
+        tree
+    }
+
+    #[test]
+    fn debug_prints_a_map() {
+        assert_eq!(
+            format!("{:?}", pretty_sample()),
+            "{1: 'a', 2: 'b', 3: 'c', 4: 'd', 5: 'e'}"
+        );
+    }
+
+    #[test]
+    fn pretty_snapshots() {
+        let tree = pretty_sample();
+        let with = |layout, color, show_size| {
+            tree.pretty_with(&PrettyOptions {
+                layout,
+                color,
+                show_size,
+            })
+        };
+        assert_eq!(
+            with(PrettyLayout::Sideways, false, true),
+            concat!(
+                "┌── 5 (1)\n",
+                "4 (5)\n",
+                "│   ┌── 3 (1)\n",
+                "└── 2 R (3)\n",
+                "    └── 1 (1)\n",
+            )
+        );
+        assert_eq!(
+            with(PrettyLayout::Sideways, true, false),
+            concat!(
+                "┌── 5\n",
+                "4\n",
+                "│   ┌── 3\n",
+                "└── \x1b[31m2\x1b[0m\n",
+                "    └── 1\n",
+            )
+        );
+        assert_eq!(
+            with(PrettyLayout::TopDown, false, true),
+            concat!(
+                "                    4 (5)\n",
+                "         ┌────────────┴─────┐\n",
+                "      2 R (3)             5 (1)\n",
+                "  ┌──────┴──────┐\n",
+                "1 (1)         3 (1)\n",
+            )
+        );
+        assert_eq!(
+            with(PrettyLayout::TopDown, true, false),
+            concat!(
+                "      4\n",
+                "  ┌───┴─┐\n",
+                "  \x1b[31m2\x1b[0m     5\n",
+                "┌─┴─┐\n",
+                "1   3\n",
+            )
+        );
+        let empty: RedBlackTree<i32, ()> = RedBlackTree::new();
+        assert_eq!(empty.pretty(), "(empty)\n");
+    }
+
+    #[test]
+    fn random_deletes_keep_invariants() {
+        let mut rng = Rng(0x8f1b_bcdc_bfa5_3e0b);
+        let mut tree = RedBlackTree::new();
+        let mut model = std::collections::BTreeMap::new();
+        for step in 0..4000 {
+            let key = rng.below(300);
+            match rng.below(4) {
+                0 | 1 => {
+                    tree.insert(key, step);
+                    model.insert(key, step);
+                }
+                2 => {
+                    tree.delete(&key);
+                    model.remove(&key);
+                }
+                _ => {
+                    tree.delete_min();
+                    if let Some(min) = model.keys().next().copied() {
+                        model.remove(&min);
+                    }
+                }
+            }
+            if step % 50 == 0 {
+                check(&tree);
+            }
+            assert_eq!(tree.tree_size() as usize, model.len());
+        }
+        check(&tree);
+        for key in 0..300 {
+            assert_eq!(tree.get(&key), model.get(&key));
+        }
+        while !tree.is_empty() {
+            tree.delete_min();
+            check(&tree);
+        }
+    }
+
+    #[test]
+    fn observer_sees_rotations_and_flips_in_order() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut tree = RedBlackTree::new();
+        tree.insert(3, ());
+        tree.insert(1, ());
+        let events = Rc::new(RefCell::new(Vec::new()));
+        tree.set_observer(Box::new(events.clone()));
+        tree.insert(2, ());
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                TreeEvent::Descend(3),
+                TreeEvent::Descend(1),
+                TreeEvent::NodeCreated(2),
+                TreeEvent::RotateLeft(1),
+                TreeEvent::RotateRight(3),
+                TreeEvent::FlipColors(2),
+            ]
+        );
+
+        events.borrow_mut().clear();
+        tree.delete(&1);
+        let events = events.borrow();
+        assert_eq!(events[0], TreeEvent::Descend(2));
+        assert!(events.contains(&TreeEvent::MoveRedLeft(2)));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|e| matches!(e, TreeEvent::NodeRemoved(_)))
+                .count(),
+            1
+        );
+        assert!(events.contains(&TreeEvent::NodeRemoved(1)));
+        check(&tree);
+    }
+
+    #[test]
+    fn select_and_rank_agree() {
+        let mut tree = RedBlackTree::new();
+        for key in (0..100).map(|k| k * 10) {
+            tree.insert(key, ());
+        }
+        assert_eq!(tree.check(), Ok(()));
+        assert_eq!(tree.select(0), 0);
+        assert_eq!(tree.select(42), 420);
+        assert_eq!(tree.select_node(100, &tree.root), None);
+        assert_eq!(tree.select_node(-1, &tree.root), None);
+        assert_eq!(tree.rank(&420), 42);
+        assert_eq!(tree.rank(&425), 43);
+        assert_eq!(tree.rank(&-5), 0);
+        assert_eq!(tree.rank(&5000), 100);
+        assert!(tree.keys().eq((0..100).map(|k| k * 10)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Select function ERROR")]
+    fn select_past_the_end_panics() {
+        let mut tree = RedBlackTree::new();
+        tree.insert(1, ());
+        tree.select(1);
+    }
+
+    #[test]
+    fn check_reports_broken_trees() {
+        let mut tree = RedBlackTree::new();
+        for key in 1..=7 {
+            tree.insert(key, ());
+        }
+        assert_eq!(tree.check(), Ok(()));
+
+        let root = tree.root.as_mut().unwrap();
+        root.right.as_mut().unwrap().color = Color::Red;
+        assert_eq!(tree.check(), Err("not a 2-3 tree"));
+
+        let root = tree.root.as_mut().unwrap();
+        root.right.as_mut().unwrap().color = Color::Black;
+        root.left.as_mut().unwrap().left.as_mut().unwrap().color = Color::Red;
+        assert_eq!(tree.check(), Err("not balanced"));
+
+        let root = tree.root.as_mut().unwrap();
+        root.size += 1;
+        assert_eq!(tree.check(), Err("subtree counts not consistent"));
+
+        let root = tree.root.as_mut().unwrap();
+        root.size -= 1;
+        root.key = 100;
+        assert_eq!(tree.check(), Err("not in symmetric order"));
+    }
+
+    #[test]
+    fn text_methods_count_chars_and_check_bounds() {
+        let mut tree = RedBlackTree::new();
+        for (key, text) in [(1, "h\u{e9}l"), (2, "lo "), (3, "\u{1f980}!")] {
+            tree.insert(key, text.to_string());
+        }
+        assert_eq!(tree.text_len(), 8);
+        assert_eq!(tree.substring(1, 7), Ok("\u{e9}llo \u{1f980}".to_string()));
+        assert_eq!(tree.slice(3..), Ok("lo \u{1f980}!".to_string()));
+        assert_eq!(tree.byte_to_char(3), Ok(2));
+        assert_eq!(
+            tree.byte_to_char(2),
+            Err(RangeError::NotCharBoundary { index: 2 })
+        );
+        assert_eq!(
+            tree.slice(0..99),
+            Err(RangeError::OutOfBounds { index: 99, len: 8 })
+        );
+        assert_eq!(
+            tree.substring(4, 2),
+            Err(RangeError::Reversed { start: 4, end: 2 })
+        );
+    }
 }
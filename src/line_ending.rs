@@ -0,0 +1,291 @@
+//NOTE: ---------------------- LINE ENDINGS -------------------------------------
+//
+// Every subtree already knows how many `\r` and `\r\n` it holds, so the counts
+// for the whole rope are read off the root, and normalizing can skip any
+// subtree that has nothing to change. Only runs of leaves that do need it are
+// rewritten, each with one split and one join.
+
+use crate::rope::{build, join_at_seam, split, Rope, RopeNode, TextInfo};
+use std::borrow::Cow;
+use std::ops::Range;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LineEndingStats {
+    pub lf: usize,   // Lone `\n`
+    pub crlf: usize, // `\r\n` pairs
+    pub cr: usize,   // Lone `\r`
+}
+
+impl LineEndingStats {
+    // The most common ending, or None if there are no line breaks. Ties go to
+    // LF, then CRLF.
+    pub fn dominant(&self) -> Option<LineEnding> {
+        if self.lf + self.crlf + self.cr == 0 {
+            None
+        } else if self.lf >= self.crlf && self.lf >= self.cr {
+            Some(LineEnding::Lf)
+        } else if self.crlf >= self.cr {
+            Some(LineEnding::CrLf)
+        } else {
+            Some(LineEnding::Cr)
+        }
+    }
+}
+
+// Whether text with these weights changes under `ending`. `prev_cr` and
+// `next_lf` describe the chars just outside it, which can complete a pair.
+fn needs_rewrite(info: &TextInfo, ending: LineEnding, prev_cr: bool, next_lf: bool) -> bool {
+    let lone_lf = info.line_breaks - info.cr - (info.starts_with_lf && prev_cr) as usize;
+    let lone_cr = info.cr - info.crlf - (info.ends_with_cr && next_lf) as usize;
+    match ending {
+        LineEnding::Lf => info.cr > 0,
+        LineEnding::Cr => info.line_breaks - info.cr + info.crlf > 0,
+        LineEnding::CrLf => lone_lf > 0 || lone_cr > 0,
+    }
+}
+
+// Rewrites every line break in `text` as `ending`. Chars inserted or deleted
+// go into `edits` as (char offset in `text`, inserted), in order.
+fn rewrite(
+    text: &str,
+    ending: LineEnding,
+    prev_cr: bool,
+    next_lf: bool,
+    edits: &mut Vec<(usize, bool)>,
+) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().enumerate().peekable();
+    let mut prev = if prev_cr { '\r' } else { '\0' };
+    while let Some((i, c)) = chars.next() {
+        let next = match chars.peek() {
+            Some(&(_, next)) => next,
+            None if next_lf => '\n',
+            None => '\0',
+        };
+        match (ending, c) {
+            (LineEnding::Lf, '\r') if next == '\n' => edits.push((i, false)),
+            (LineEnding::Lf, '\r') => out.push('\n'),
+            (LineEnding::Cr, '\n') if prev == '\r' => edits.push((i, false)),
+            (LineEnding::Cr, '\n') => out.push('\r'),
+            (LineEnding::CrLf, '\r') => {
+                out.push('\r');
+                if next != '\n' {
+                    out.push('\n');
+                    edits.push((i + 1, true));
+                }
+            }
+            (LineEnding::CrLf, '\n') => {
+                if prev != '\r' {
+                    out.push('\r');
+                    edits.push((i, true));
+                }
+                out.push('\n');
+            }
+            _ => out.push(c),
+        }
+        prev = c;
+    }
+    out
+}
+
+pub(crate) fn convert(text: &str, ending: LineEnding) -> Cow<'_, str> {
+    if text.contains(['\r', '\n']) {
+        Cow::Owned(rewrite(text, ending, false, false, &mut Vec::new()))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+// Char ranges of the leaves that change, with neighbouring leaves merged.
+fn dirty_runs(
+    node: &RopeNode,
+    ending: LineEnding,
+    prev_cr: bool,
+    next_lf: bool,
+    offset: usize,
+    runs: &mut Vec<Range<usize>>,
+) {
+    if !needs_rewrite(&node.info, ending, prev_cr, next_lf) {
+        return;
+    }
+    if node.is_leaf() {
+        match runs.last_mut() {
+            Some(run) if run.end == offset => run.end += node.info.chars,
+            _ => runs.push(offset..offset + node.info.chars),
+        }
+        return;
+    }
+    let (left, right) = (
+        node.left.as_deref().unwrap(),
+        node.right.as_deref().unwrap(),
+    );
+    dirty_runs(
+        left,
+        ending,
+        prev_cr,
+        right.info.starts_with_lf,
+        offset,
+        runs,
+    );
+    let offset = offset + left.info.chars;
+    dirty_runs(right, ending, left.info.ends_with_cr, next_lf, offset, runs);
+}
+
+impl Rope {
+    pub fn line_ending_stats(&self) -> LineEndingStats {
+        let info = self.root.as_ref().map_or(TextInfo::default(), |n| n.info);
+        LineEndingStats {
+            lf: info.line_breaks - info.cr,
+            crlf: info.crlf,
+            cr: info.cr - info.crlf,
+        }
+    }
+
+    // Rewrites every line break as `ending`. Returns whether anything changed.
+    pub fn normalize_line_endings(&mut self, ending: LineEnding) -> bool {
+        let mut runs = Vec::new();
+        if let Some(root) = self.root.as_deref() {
+            dirty_runs(root, ending, false, false, 0, &mut runs);
+        }
+        // From the back, so the runs still to do keep their positions. The
+        // text around a run belongs to clean leaves and is never rewritten.
+        for run in runs.iter().rev() {
+            let prev_cr = run.start > 0 && self.char_at(run.start - 1) == Some('\r');
+            let next_lf = self.char_at(run.end) == Some('\n');
+            let mut edits = Vec::new();
+            let text = self.slice(run.clone()).to_string();
+            let text = rewrite(&text, ending, prev_cr, next_lf, &mut edits);
+            for &(at, inserted) in edits.iter().rev() {
+                let at = run.start + at;
+                if inserted {
+                    self.anchors.insert(at, 1);
+                } else {
+                    self.anchors.remove(at, at + 1);
+                }
+            }
+            let (left, rest) = split(self.root.take(), run.start);
+            let (_, right) = split(rest, run.len());
+            self.root = join_at_seam(join_at_seam(left, build(&text)), right);
+        }
+        !runs.is_empty()
+    }
+
+    // With Some(ending), `insert` rewrites the line breaks of the text it is
+    // given as `ending` first. Breaks in the text are converted on their own,
+    // so inserting between a `\r` and `\n` already in the rope is left alone.
+    pub fn set_insert_line_ending(&mut self, ending: Option<LineEnding>) {
+        self.insert_line_ending = ending;
+    }
+
+    pub fn insert_line_ending(&self) -> Option<LineEnding> {
+        self.insert_line_ending
+    }
+
+    // `text` as `insert` would store it.
+    pub(crate) fn converted<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        match self.insert_line_ending {
+            Some(ending) => convert(text, ending),
+            None => Cow::Borrowed(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::changeset::Bias;
+    use crate::rope::tests::{check, from_chunks};
+    use crate::tests::Rng;
+
+    fn normalized(text: &str, ending: LineEnding) -> String {
+        text.replace("\r\n", "\n")
+            .replace('\r', "\n")
+            .replace('\n', ending.as_str())
+    }
+
+    #[test]
+    fn stats_count_a_pair_split_between_leaves_once() {
+        let rope = from_chunks(&["a\r", "\nb\r", "c\n\n"]);
+        assert_eq!(
+            rope.line_ending_stats(),
+            LineEndingStats {
+                lf: 2,
+                crlf: 1,
+                cr: 1
+            }
+        );
+        assert_eq!(rope.line_ending_stats().dominant(), Some(LineEnding::Lf));
+        assert_eq!(Rope::from("none").line_ending_stats().dominant(), None);
+        assert_eq!(
+            Rope::from("a\r\nb\rc\r\n").line_ending_stats().dominant(),
+            Some(LineEnding::CrLf)
+        );
+    }
+
+    #[test]
+    fn normalize_matches_a_string() {
+        let mut rng = Rng(0x428a_2f98_d728_ae22);
+        let pieces = ["abc", "\r", "\n", "\r\n", "\u{e9}", &"x".repeat(700)];
+        for ending in [LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr] {
+            for _ in 0..10 {
+                let text: String = (0..rng.below(400))
+                    .map(|_| pieces[rng.below(pieces.len())])
+                    .collect();
+                let mut rope = Rope::from(text.as_str());
+                let expected = normalized(&text, ending);
+                assert_eq!(rope.normalize_line_endings(ending), expected != text);
+                check(&rope);
+                assert_eq!(rope.to_string(), expected);
+                assert!(!rope.normalize_line_endings(ending));
+            }
+        }
+    }
+
+    #[test]
+    fn normalize_moves_anchors_with_the_text() {
+        let mut rope = from_chunks(&["a\r", "\nb\n", "c"]);
+        let c = rope.create_anchor(5, Bias::Left);
+        let b = rope.create_anchor(3, Bias::Left);
+        rope.normalize_line_endings(LineEnding::Lf);
+        assert_eq!(rope.to_string(), "a\nb\nc");
+        assert_eq!(rope.anchor_position(&b), Some(2));
+        assert_eq!(rope.anchor_position(&c), Some(4));
+        rope.normalize_line_endings(LineEnding::CrLf);
+        assert_eq!(rope.to_string(), "a\r\nb\r\nc");
+        assert_eq!(rope.anchor_position(&b), Some(3));
+        assert_eq!(rope.anchor_position(&c), Some(6));
+    }
+
+    #[test]
+    fn insert_converts_only_the_new_text() {
+        let mut rope = Rope::from("a\rb");
+        assert_eq!(rope.insert_line_ending(), None);
+        rope.set_insert_line_ending(Some(LineEnding::CrLf));
+        rope.insert(3, "\nc\rd\r\n");
+        assert_eq!(rope.to_string(), "a\rb\r\nc\r\nd\r\n");
+        // Between a \r and \n already in the rope
+        rope.insert(4, "\n");
+        assert_eq!(rope.to_string(), "a\rb\r\r\n\nc\r\nd\r\n");
+        rope.set_insert_line_ending(None);
+        rope.insert(0, "\r");
+        assert_eq!(rope.to_string(), "\ra\rb\r\r\n\nc\r\nd\r\n");
+    }
+}
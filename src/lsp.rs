@@ -0,0 +1,233 @@
+//NOTE: ---------------------- LSP POSITIONS AND EDITS ---------------------------
+//
+// The Language Server Protocol addresses text as (line, UTF-16 code unit).
+// Both halves map onto rope weights, so conversions stay O(log n).
+
+use crate::rope::Rope;
+use crate::RangeError;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize, // UTF-16 code units from the start of the line
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LspRange {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: LspRange,
+    pub new_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    BadPosition { edit: usize, error: RangeError },
+    Reversed { edit: usize },
+    Overlapping { first: usize, second: usize },
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::BadPosition { edit, error } => write!(f, "edit {}: {}", edit, error),
+            EditError::Reversed { edit } => write!(f, "edit {}: range end is before start", edit),
+            EditError::Overlapping { first, second } => {
+                write!(f, "edits {} and {} overlap", first, second)
+            }
+        }
+    }
+}
+
+impl Error for EditError {}
+
+impl Rope {
+    // A column past the end of the line clamps to the end of its text, as the
+    // protocol asks; the line itself has to exist.
+    pub fn position_to_char(&self, line: usize, utf16_col: usize) -> Result<usize, RangeError> {
+        if line >= self.len_lines() {
+            return Err(RangeError::OutOfBounds {
+                index: line,
+                len: self.len_lines(),
+            });
+        }
+        let start = self.line_to_char(line);
+        let end = self.line_content_end(line);
+        let target = self.char_to_utf16(start) + utf16_col;
+        if target >= self.char_to_utf16(end) {
+            return Ok(end);
+        }
+        Ok(self.utf16_to_char(target))
+    }
+
+    pub fn char_to_position(&self, char_idx: usize) -> Position {
+        let line = self.char_to_line(char_idx);
+        let start = self.line_to_char(line);
+        Position {
+            line,
+            character: self.char_to_utf16(char_idx) - self.char_to_utf16(start),
+        }
+    }
+
+    // Applies edits whose ranges all refer to the text before the batch, in
+    // any order. Inserts at the same position land in slice order. Nothing is
+    // changed unless every edit is valid.
+    pub fn apply_lsp_edits(&mut self, edits: &[TextEdit]) -> Result<(), EditError> {
+        let mut resolved = Vec::with_capacity(edits.len());
+        for (i, edit) in edits.iter().enumerate() {
+            let to_char = |p: Position| {
+                self.position_to_char(p.line, p.character)
+                    .map_err(|error| EditError::BadPosition { edit: i, error })
+            };
+            let start = to_char(edit.range.start)?;
+            let end = to_char(edit.range.end)?;
+            if start > end {
+                return Err(EditError::Reversed { edit: i });
+            }
+            resolved.push((start, end, i));
+        }
+
+        resolved.sort();
+        for pair in resolved.windows(2) {
+            let (_, prev_end, first) = pair[0];
+            let (start, _, second) = pair[1];
+            if start < prev_end {
+                return Err(EditError::Overlapping { first, second });
+            }
+        }
+
+        // Back to front, so earlier offsets stay valid.
+        for &(start, end, i) in resolved.iter().rev() {
+            self.remove(start..end);
+            self.insert(start, &edits[i].new_text);
+        }
+        Ok(())
+    }
+
+    // Char index just before the line break that ends `line`.
+    fn line_content_end(&self, line: usize) -> usize {
+        let mut end = self.line_to_char(line + 1);
+        let start = self.line_to_char(line);
+        if end > start && self.char_at(end - 1) == Some('\n') {
+            end -= 1;
+        }
+        if end > start && self.char_at(end - 1) == Some('\r') {
+            end -= 1;
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rope::tests::{check, from_chunks};
+
+    fn range(start: (usize, usize), end: (usize, usize)) -> LspRange {
+        LspRange {
+            start: Position {
+                line: start.0,
+                character: start.1,
+            },
+            end: Position {
+                line: end.0,
+                character: end.1,
+            },
+        }
+    }
+
+    #[test]
+    fn positions_across_a_crlf_seam() {
+        // "a🦀\r\nb" with the CRLF split between two leaves
+        let rope = from_chunks(&["a\u{1f980}\r", "\nb\u{e9}"]);
+        assert_eq!(rope.len_utf16(), 7);
+        assert_eq!(rope.char_to_utf16(2), 3);
+        assert_eq!(rope.utf16_to_char(3), 2);
+        assert_eq!(rope.utf16_to_char(2), 1); // Low half of the surrogate pair
+
+        assert_eq!(rope.position_to_char(0, 3), Ok(2));
+        assert_eq!(rope.position_to_char(0, 99), Ok(2)); // Clamped before the break
+        assert_eq!(rope.position_to_char(1, 1), Ok(5));
+        assert_eq!(
+            rope.position_to_char(2, 0),
+            Err(RangeError::OutOfBounds { index: 2, len: 2 })
+        );
+        assert_eq!(
+            rope.char_to_position(3),
+            Position {
+                line: 0,
+                character: 4
+            }
+        );
+        assert_eq!(
+            rope.char_to_position(4),
+            Position {
+                line: 1,
+                character: 0
+            }
+        );
+        assert_eq!(
+            rope.char_to_position(6),
+            Position {
+                line: 1,
+                character: 2
+            }
+        );
+    }
+
+    #[test]
+    fn edits_apply_against_the_original_text() {
+        let mut rope = from_chunks(&["fn f\u{1f980}() {\r", "\n    x\r\n}\r\n"]);
+        let edits = [
+            TextEdit {
+                range: range((1, 4), (1, 5)),
+                new_text: "y + 1".to_string(),
+            },
+            TextEdit {
+                range: range((0, 3), (0, 6)),
+                new_text: "g".to_string(),
+            },
+            TextEdit {
+                range: range((2, 1), (2, 1)),
+                new_text: " // end".to_string(),
+            },
+        ];
+        rope.apply_lsp_edits(&edits).unwrap();
+        check(&rope);
+        assert_eq!(rope.to_string(), "fn g() {\r\n    y + 1\r\n} // end\r\n");
+    }
+
+    #[test]
+    fn bad_batches_change_nothing() {
+        let mut rope = Rope::from("one\ntwo\n");
+        let edit = |start, end| TextEdit {
+            range: range(start, end),
+            new_text: String::new(),
+        };
+        assert_eq!(
+            rope.apply_lsp_edits(&[edit((0, 0), (1, 2)), edit((1, 1), (1, 3))]),
+            Err(EditError::Overlapping {
+                first: 0,
+                second: 1
+            })
+        );
+        assert_eq!(
+            rope.apply_lsp_edits(&[edit((1, 2), (0, 1))]),
+            Err(EditError::Reversed { edit: 0 })
+        );
+        assert_eq!(
+            rope.apply_lsp_edits(&[edit((0, 0), (0, 1)), edit((5, 0), (5, 0))]),
+            Err(EditError::BadPosition {
+                edit: 1,
+                error: RangeError::OutOfBounds { index: 5, len: 3 }
+            })
+        );
+        assert_eq!(rope.to_string(), "one\ntwo\n");
+    }
+}
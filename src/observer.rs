@@ -0,0 +1,36 @@
+//NOTE: ---------------------- OPERATION TRACING ---------------------------------
+//
+// Events a tree reports while it inserts and deletes, in the order they
+// happen. Nothing is built or cloned unless an observer is set.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeEvent<K> {
+    Descend(K),     // Visited a node on the way down
+    RotateLeft(K),  // Key of the node that moved down
+    RotateRight(K), // Key of the node that moved down
+    FlipColors(K),  // Key of the parent whose children were recolored
+    MoveRedLeft(K),
+    MoveRedRight(K),
+    NodeCreated(K),
+    NodeRemoved(K), // Key that left the tree
+}
+
+pub trait TreeObserver<K> {
+    fn on_event(&mut self, event: TreeEvent<K>);
+}
+
+// Lets callers keep a handle on the observer they gave to the tree.
+impl<K, O: TreeObserver<K>> TreeObserver<K> for Rc<RefCell<O>> {
+    fn on_event(&mut self, event: TreeEvent<K>) {
+        self.borrow_mut().on_event(event);
+    }
+}
+
+impl<K> TreeObserver<K> for Vec<TreeEvent<K>> {
+    fn on_event(&mut self, event: TreeEvent<K>) {
+        self.push(event);
+    }
+}
@@ -0,0 +1,397 @@
+//NOTE: ---------------------- OPERATION LOG ------------------------------------
+//
+// Records every mutating call on a tree so a run can be reproduced later.
+// The log is text, one operation per line, fields separated by a single
+// space and percent-escaped:
+//
+//     rbtree-log 1
+//     insert <key> <value>
+//     delete <key>
+//     delete_min
+//     split <key>
+//     append <key> <value> <key> <value> ...
+//
+// A split keeps the lower half in the tree; the upper half it hands back is
+// not recorded. An append lists the entries it moved in, in key order.
+
+use crate::RedBlackTree;
+use std::error::Error;
+use std::fmt::{self, Display, Write};
+use std::str::FromStr;
+
+const LOG_HEADER: &str = "rbtree-log";
+const LOG_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeOp<K, V> {
+    Insert(K, V),
+    Delete(K),
+    DeleteMin,
+    Split(K),
+    Append(Vec<(K, V)>),
+}
+
+impl<K: Display, V: Display> Display for TreeOp<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeOp::Insert(k, v) => write!(
+                f,
+                "insert {} {}",
+                log_escape(&k.to_string()),
+                log_escape(&v.to_string())
+            ),
+            TreeOp::Delete(k) => write!(f, "delete {}", log_escape(&k.to_string())),
+            TreeOp::DeleteMin => write!(f, "delete_min"),
+            TreeOp::Split(k) => write!(f, "split {}", log_escape(&k.to_string())),
+            TreeOp::Append(entries) => {
+                write!(f, "append")?;
+                for (k, v) in entries {
+                    write!(
+                        f,
+                        " {} {}",
+                        log_escape(&k.to_string()),
+                        log_escape(&v.to_string())
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<K: FromStr, V: FromStr> FromStr for TreeOp<K, V> {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let key = |field: &str| {
+            K::from_str(&log_unescape(field)?).map_err(|_| format!("bad key `{}`", field))
+        };
+        let value = |field: &str| {
+            V::from_str(&log_unescape(field)?).map_err(|_| format!("bad value `{}`", field))
+        };
+        match fields.as_slice() {
+            ["insert", k, v] => Ok(TreeOp::Insert(key(k)?, value(v)?)),
+            ["delete", k] => Ok(TreeOp::Delete(key(k)?)),
+            ["delete_min"] => Ok(TreeOp::DeleteMin),
+            ["split", k] => Ok(TreeOp::Split(key(k)?)),
+            ["append", rest @ ..] if rest.len() % 2 == 0 => rest
+                .chunks(2)
+                .map(|pair| Ok((key(pair[0])?, value(pair[1])?)))
+                .collect::<Result<_, String>>()
+                .map(TreeOp::Append),
+            _ => Err(format!("unknown operation `{}`", line)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    MissingHeader,
+    UnsupportedVersion(String),
+    Parse { line: usize, message: String },
+    Invariant { op: usize, invariant: &'static str },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::MissingHeader => write!(f, "missing `{}` header", LOG_HEADER),
+            ReplayError::UnsupportedVersion(v) => write!(f, "unsupported log version `{}`", v),
+            ReplayError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ReplayError::Invariant { op, invariant } => {
+                write!(f, "after operation {}: {}", op, invariant)
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+// Wraps a tree and keeps every mutating call.
+pub struct RecordingTree<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    tree: RedBlackTree<K, V>,
+    ops: Vec<TreeOp<K, V>>,
+}
+
+impl<K: Ord + Clone, V: Clone> RecordingTree<K, V> {
+    pub fn new() -> Self {
+        RecordingTree {
+            tree: RedBlackTree::new(),
+            ops: Vec::new(),
+        }
+    }
+
+    pub fn tree(&self) -> &RedBlackTree<K, V> {
+        &self.tree
+    }
+
+    pub fn ops(&self) -> &[TreeOp<K, V>] {
+        &self.ops
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.ops.push(TreeOp::Insert(key.clone(), value.clone()));
+        self.tree.insert(key, value);
+    }
+
+    pub fn delete(&mut self, key: &K) {
+        self.ops.push(TreeOp::Delete(key.clone()));
+        self.tree.delete(key);
+    }
+
+    pub fn delete_min(&mut self) {
+        self.ops.push(TreeOp::DeleteMin);
+        self.tree.delete_min();
+    }
+
+    pub fn split(&mut self, key: &K) -> RedBlackTree<K, V> {
+        self.ops.push(TreeOp::Split(key.clone()));
+        self.tree.split(key)
+    }
+
+    pub fn append(&mut self, other: &mut RedBlackTree<K, V>) {
+        let mut entries = Vec::new();
+        other.in_order(&other.root, &mut entries);
+        let entries = entries
+            .into_iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        self.ops.push(TreeOp::Append(entries));
+        self.tree.append(other);
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for RecordingTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone + Display, V: Clone + Display> RecordingTree<K, V> {
+    pub fn to_log(&self) -> String {
+        let mut log = format!("{} {}\n", LOG_HEADER, LOG_VERSION);
+        for op in &self.ops {
+            let _ = writeln!(log, "{}", op);
+        }
+        log
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> RedBlackTree<K, V> {
+    pub fn apply(&mut self, op: &TreeOp<K, V>) {
+        match op {
+            TreeOp::Insert(k, v) => self.insert(k.clone(), v.clone()),
+            TreeOp::Delete(k) => self.delete(k),
+            TreeOp::DeleteMin => self.delete_min(),
+            TreeOp::Split(k) => {
+                self.split(k);
+            }
+            TreeOp::Append(entries) => {
+                let mut other = RedBlackTree::new();
+                for (k, v) in entries {
+                    other.insert(k.clone(), v.clone());
+                }
+                self.append(&mut other);
+            }
+        }
+    }
+}
+
+pub fn parse_log<K: FromStr, V: FromStr>(log: &str) -> Result<Vec<TreeOp<K, V>>, ReplayError> {
+    let mut lines = log.lines().enumerate();
+    let header = lines.next().map_or("", |(_, l)| l);
+    match header.split_once(' ') {
+        Some((LOG_HEADER, version)) if version == LOG_VERSION.to_string() => {}
+        Some((LOG_HEADER, version)) => {
+            return Err(ReplayError::UnsupportedVersion(version.to_string()))
+        }
+        _ => return Err(ReplayError::MissingHeader),
+    }
+
+    let mut ops = Vec::new();
+    for (i, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        let op = line.parse().map_err(|message| ReplayError::Parse {
+            line: i + 1,
+            message,
+        })?;
+        ops.push(op);
+    }
+    Ok(ops)
+}
+
+// Rebuilds the tree from a log, checking the invariants after every step.
+// `stop_at` limits the replay to the first N operations.
+pub fn replay<K, V>(log: &str, stop_at: Option<usize>) -> Result<RedBlackTree<K, V>, ReplayError>
+where
+    K: Ord + Clone + FromStr,
+    V: Clone + FromStr,
+{
+    let ops = parse_log::<K, V>(log)?;
+    let mut tree = RedBlackTree::new();
+    for (i, op) in ops.iter().take(stop_at.unwrap_or(ops.len())).enumerate() {
+        tree.apply(op);
+        tree.check().map_err(|invariant| ReplayError::Invariant {
+            op: i + 1,
+            invariant,
+        })?;
+    }
+    Ok(tree)
+}
+
+fn log_escape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '%' => out.push_str("%25"),
+            ' ' => out.push_str("%20"),
+            '\t' => out.push_str("%09"),
+            '\n' => out.push_str("%0A"),
+            '\r' => out.push_str("%0D"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn log_unescape(field: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(i) = rest.find('%') {
+        out.push_str(&rest[..i]);
+        // from_str_radix would also take a sign, as in `%+1`
+        let code = rest
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| format!("bad escape in `{}`", field))?;
+        out.push(code as char);
+        rest = &rest[i + 3..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Rng;
+
+    fn entries(tree: &RedBlackTree<i32, String>) -> Vec<(i32, String)> {
+        let mut out = Vec::new();
+        tree.in_order(&tree.root, &mut out);
+        out.into_iter().map(|(k, v)| (*k, v.clone())).collect()
+    }
+
+    fn record(rng: &mut Rng, steps: usize) -> RecordingTree<i32, String> {
+        let mut rec = RecordingTree::new();
+        for step in 0..steps {
+            let key = rng.below(200) as i32;
+            match rng.below(10) {
+                0..=4 => rec.insert(key, format!("v {}%\n{}", key, step)),
+                5 | 6 => rec.delete(&key),
+                7 => rec.delete_min(),
+                8 => {
+                    rec.split(&key);
+                }
+                _ => {
+                    let mut other = RedBlackTree::new();
+                    for _ in 0..rng.below(5) {
+                        let k = rng.below(400) as i32 - 100;
+                        other.insert(k, format!("a{}", step));
+                    }
+                    rec.append(&mut other);
+                    assert!(other.is_empty());
+                }
+            }
+        }
+        rec
+    }
+
+    #[test]
+    fn log_round_trips_and_replays_to_any_prefix() {
+        let mut rng = Rng(0xd1b5_4a32_d192_ed03);
+        let rec = record(&mut rng, 400);
+        let log = rec.to_log();
+        assert!(log.starts_with("rbtree-log 1\n"));
+        assert!(log.contains("\nsplit ") && log.contains("\nappend "));
+
+        let ops = parse_log::<i32, String>(&log).unwrap();
+        assert_eq!(ops, rec.ops());
+
+        let full = replay::<i32, String>(&log, None).unwrap();
+        assert_eq!(entries(&full), entries(rec.tree()));
+
+        for stop in [0, 1, 57, 200, 399] {
+            let mut expected = RedBlackTree::new();
+            for op in &ops[..stop] {
+                expected.apply(op);
+            }
+            let replayed = replay::<i32, String>(&log, Some(stop)).unwrap();
+            assert_eq!(entries(&replayed), entries(&expected));
+        }
+    }
+
+    #[test]
+    fn split_and_append_move_entries() {
+        let mut tree = RedBlackTree::new();
+        for key in 0..20 {
+            tree.insert(key, key.to_string());
+        }
+        let mut upper = tree.split(&12);
+        assert!(tree.keys().eq(0..12));
+        assert!(upper.keys().eq(12..20));
+        assert_eq!((tree.check(), upper.check()), (Ok(()), Ok(())));
+
+        upper.insert(3, "three".to_string());
+        tree.append(&mut upper);
+        assert!(upper.is_empty());
+        assert!(tree.keys().eq(0..20));
+        assert_eq!(tree.get(&3).map(String::as_str), Some("three"));
+        assert_eq!(tree.check(), Ok(()));
+    }
+
+    #[test]
+    fn bad_logs_are_rejected() {
+        assert_eq!(
+            parse_log::<i32, i32>("insert 1 2\n"),
+            Err(ReplayError::MissingHeader)
+        );
+        assert_eq!(
+            parse_log::<i32, i32>("rbtree-log 2\n"),
+            Err(ReplayError::UnsupportedVersion("2".to_string()))
+        );
+        assert_eq!(
+            parse_log::<i32, i32>("rbtree-log 1\ninsert 1 2\n\nappend 1\n"),
+            Err(ReplayError::Parse {
+                line: 4,
+                message: "unknown operation `append 1`".to_string()
+            })
+        );
+        assert_eq!(
+            parse_log::<i32, i32>("rbtree-log 1\ndelete x\n"),
+            Err(ReplayError::Parse {
+                line: 2,
+                message: "bad key `x`".to_string()
+            })
+        );
+        assert_eq!(
+            parse_log::<i32, String>("rbtree-log 1\ninsert 1 %+1\n"),
+            Err(ReplayError::Parse {
+                line: 2,
+                message: "bad escape in `%+1`".to_string()
+            })
+        );
+        assert_eq!(
+            parse_log::<i32, String>("rbtree-log 1\nappend\nsplit 4\n"),
+            Ok(vec![TreeOp::Append(Vec::new()), TreeOp::Split(4)])
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//NOTE: ---------------------- DIFFERENTIAL ORACLE ------------------------------
+//
+// Runs every operation on both a tree and a BTreeMap and compares the ordered
+// API after each call, reporting the first answer that differs along with
+// the operations that led to it. The order-statistic answers the map lacks
+// (rank, select, floor, ceiling, size_size) are worked out from its sorted
+// keys.
+
+use crate::{RedBlackTree, TreeOp};
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence<K, V> {
+    pub query: String,
+    pub expected: String,
+    pub actual: String,
+    pub history: Vec<TreeOp<K, V>>,
+}
+
+impl<K: Display, V: Display> Display for Divergence<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: expected {}, got {}",
+            self.query, self.expected, self.actual
+        )?;
+        writeln!(f, "after {} operations:", self.history.len())?;
+        for op in &self.history {
+            writeln!(f, "    {}", op)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct CheckedRedBlackTree<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    tree: RedBlackTree<K, V>,
+    oracle: BTreeMap<K, V>,
+    history: Vec<TreeOp<K, V>>,
+}
+
+impl<K, V> CheckedRedBlackTree<K, V>
+where
+    K: Ord + Clone + Debug,
+    V: Clone + PartialEq + Debug,
+{
+    pub fn new() -> Self {
+        CheckedRedBlackTree {
+            tree: RedBlackTree::new(),
+            oracle: BTreeMap::new(),
+            history: Vec::new(),
+        }
+    }
+
+    pub fn tree(&self) -> &RedBlackTree<K, V> {
+        &self.tree
+    }
+
+    pub fn oracle(&self) -> &BTreeMap<K, V> {
+        &self.oracle
+    }
+
+    pub fn history(&self) -> &[TreeOp<K, V>] {
+        &self.history
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Result<(), Divergence<K, V>> {
+        self.history
+            .push(TreeOp::Insert(key.clone(), value.clone()));
+        self.oracle.insert(key.clone(), value.clone());
+        self.tree.insert(key.clone(), value);
+        self.verify(Some(&key))
+    }
+
+    pub fn delete(&mut self, key: &K) -> Result<(), Divergence<K, V>> {
+        self.history.push(TreeOp::Delete(key.clone()));
+        self.oracle.remove(key);
+        self.tree.delete(key);
+        self.verify(Some(key))
+    }
+
+    pub fn delete_min(&mut self) -> Result<(), Divergence<K, V>> {
+        self.history.push(TreeOp::DeleteMin);
+        let min = self.oracle.pop_first().map(|(k, _)| k);
+        self.tree.delete_min();
+        self.verify(min.as_ref())
+    }
+
+    // Drops the upper half, as a replayed split does.
+    pub fn split(&mut self, key: &K) -> Result<(), Divergence<K, V>> {
+        self.history.push(TreeOp::Split(key.clone()));
+        self.oracle.split_off(key);
+        self.tree.split(key);
+        self.verify(Some(key))
+    }
+
+    pub fn append(&mut self, entries: Vec<(K, V)>) -> Result<(), Divergence<K, V>> {
+        let mut other = RedBlackTree::new();
+        for (k, v) in &entries {
+            self.oracle.insert(k.clone(), v.clone());
+            other.insert(k.clone(), v.clone());
+        }
+        self.history.push(TreeOp::Append(entries));
+        self.tree.append(&mut other);
+        self.verify(None)
+    }
+
+    // Compares every query against the oracle; `touched` is the key of the
+    // last operation, which is probed even when it is no longer present.
+    pub fn verify(&self, touched: Option<&K>) -> Result<(), Divergence<K, V>> {
+        let tree = &self.tree;
+        self.expect(
+            "tree_size()".to_string(),
+            self.oracle.len() as i32,
+            tree.tree_size(),
+        )?;
+        self.expect("check()".to_string(), Ok(()), tree.check())?;
+
+        let sorted: Vec<K> = self.oracle.keys().cloned().collect();
+        self.expect("keys()".to_string(), sorted.clone(), tree.keys().collect())?;
+        if let (Some(first), Some(last)) = (sorted.first(), sorted.last()) {
+            self.expect("min()".to_string(), first.clone(), tree.min())?;
+            self.expect("max()".to_string(), last.clone(), tree.max())?;
+        }
+        for (i, k) in sorted.iter().enumerate() {
+            self.expect(format!("select({})", i), k.clone(), tree.select(i as i32))?;
+        }
+
+        let mut probes = sorted.clone();
+        if let Some(k) = touched {
+            if !self.oracle.contains_key(k) {
+                probes.push(k.clone());
+            }
+        }
+        for k in &probes {
+            self.expect(format!("get({:?})", k), self.oracle.get(k), tree.get(k))?;
+            self.expect(
+                format!("rank({:?})", k),
+                self.oracle.range(..k).count() as i32,
+                tree.rank(k),
+            )?;
+            // floor and ceiling panic when there is no answer, which rank
+            // and size_size already cover
+            if let Some((floor, _)) = self.oracle.range(..=k).next_back() {
+                self.expect(format!("floor({:?})", k), floor.clone(), tree.floor(k))?;
+            }
+            if let Some((ceiling, _)) = self.oracle.range(k..).next() {
+                self.expect(
+                    format!("ceiling({:?})", k),
+                    ceiling.clone(),
+                    tree.ceiling(k),
+                )?;
+            }
+        }
+
+        if let (Some(lo), Some(hi)) = (sorted.first(), sorted.last()) {
+            for k in &probes {
+                for (a, b) in [(lo, k), (k, hi), (k, lo)] {
+                    let in_range: Vec<K> = if a <= b {
+                        self.oracle.range(a..=b).map(|(k, _)| k.clone()).collect()
+                    } else {
+                        Vec::new()
+                    };
+                    self.expect(
+                        format!("size_size({:?}, {:?})", a, b),
+                        in_range.len() as i32,
+                        tree.size_size(a, b),
+                    )?;
+                    self.expect(
+                        format!("keys_range({:?}, {:?})", a, b),
+                        in_range,
+                        tree.keys_range(a.clone(), b.clone()).collect(),
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn expect<T: PartialEq + Debug>(
+        &self,
+        query: String,
+        expected: T,
+        actual: T,
+    ) -> Result<(), Divergence<K, V>> {
+        if expected == actual {
+            return Ok(());
+        }
+        Err(Divergence {
+            query,
+            expected: format!("{:?}", expected),
+            actual: format!("{:?}", actual),
+            history: self.history.clone(),
+        })
+    }
+}
+
+impl<K, V> Default for CheckedRedBlackTree<K, V>
+where
+    K: Ord + Clone + Debug,
+    V: Clone + PartialEq + Debug,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Rng;
+
+    #[test]
+    fn random_operations_never_diverge() {
+        let mut rng = Rng(0x6a09_e667_f3bc_c908);
+        let mut checked = CheckedRedBlackTree::new();
+        for step in 0..1500 {
+            let key = rng.below(120) as i32;
+            let result = match rng.below(10) {
+                0..=4 => checked.insert(key, step),
+                5 | 6 => checked.delete(&key),
+                7 => checked.delete_min(),
+                8 => checked.split(&(key + 20)),
+                _ => {
+                    let entries = (0..rng.below(6))
+                        .map(|_| (rng.below(120) as i32, step))
+                        .collect();
+                    checked.append(entries)
+                }
+            };
+            if let Err(divergence) = result {
+                panic!("{}", divergence);
+            }
+        }
+        assert_eq!(checked.history().len(), 1500);
+        assert_eq!(checked.tree().tree_size() as usize, checked.oracle().len());
+    }
+
+    #[test]
+    fn empty_tree_queries() {
+        let mut checked: CheckedRedBlackTree<i32, i32> = CheckedRedBlackTree::new();
+        assert_eq!(checked.delete_min(), Ok(()));
+        assert_eq!(checked.delete(&3), Ok(()));
+        assert_eq!(checked.tree().rank(&3), 0);
+        assert_eq!(checked.tree().size_size(&0, &9), 0);
+        assert_eq!(checked.tree().keys().count(), 0);
+        assert_eq!(checked.tree().keys_range(0, 9).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Empty Tree")]
+    fn min_of_an_empty_tree_panics() {
+        let checked: CheckedRedBlackTree<i32, i32> = CheckedRedBlackTree::new();
+        checked.tree().min();
+    }
+
+    #[test]
+    fn divergence_lists_the_history() {
+        let mut checked = CheckedRedBlackTree::new();
+        checked.insert(2, 20).unwrap();
+        checked.insert(1, 10).unwrap();
+        // Change the tree behind the oracle's back
+        checked.tree.insert(3, 30);
+        let divergence = checked.verify(None).unwrap_err();
+        assert_eq!(divergence.query, "tree_size()");
+        assert_eq!(
+            divergence.to_string(),
+            "tree_size(): expected 2, got 3\n\
+             after 2 operations:\n    insert 2 20\n    insert 1 10\n"
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//NOTE: ---------------------- PATCHES ------------------------------------------
+//
+// Applies unified diffs like `patch` does. Each hunk is looked for at the line
+// its header names, shifted by how far earlier hunks landed from theirs; if
+// the text isn't there, nearby lines are tried, closest first, and then the
+// outer context lines are dropped one at a time (fuzz). Every hunk is placed
+// before anything is edited, so a patch either applies whole or not at all.
+
+use crate::diff::text_lines;
+use crate::rope::Rope;
+use crate::slice::RopeSlice;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct PatchOptions {
+    pub max_fuzz: usize,   // Context lines that may be ignored at each end
+    pub max_offset: usize, // How many lines from its expected place a hunk may move
+}
+
+impl Default for PatchOptions {
+    fn default() -> Self {
+        PatchOptions {
+            max_fuzz: 2,
+            max_offset: usize::MAX, // The whole file, as `patch` searches
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HunkOutcome {
+    Applied,
+    Offset { offset: isize },
+    Fuzzed { offset: isize, fuzz: usize },
+    Rejected,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchReport {
+    pub hunks: Vec<HunkOutcome>, // In patch order
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    // 1-based line of the patch text that could not be parsed.
+    Malformed { line: usize },
+    // At least one hunk did not fit; the rope was not changed.
+    Rejected(PatchReport),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Malformed { line } => write!(f, "malformed patch at line {}", line),
+            PatchError::Rejected(report) => {
+                let rejected = report
+                    .hunks
+                    .iter()
+                    .filter(|h| **h == HunkOutcome::Rejected)
+                    .count();
+                write!(f, "{} of {} hunks rejected", rejected, report.hunks.len())
+            }
+        }
+    }
+}
+
+impl Error for PatchError {}
+
+//NOTE:***************************************************************************
+//   *  Parsing
+//   ***************************************************************************
+struct PatchHunk {
+    old_start: usize, // 0-based line where `old` begins
+    old: Vec<String>, // Context and removed lines, with their line breaks
+    new: Vec<String>, // Context and added lines
+    leading: usize,   // Context lines before the first change
+    trailing: usize,  // Context lines after the last change
+}
+
+// "a" or "a,b" from a hunk header, as (start, count).
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    match text.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((text.parse().ok()?, 1)),
+    }
+}
+
+fn parse_header(line: &str) -> Option<((usize, usize), (usize, usize))> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (old, rest) = rest.split_once(" +")?;
+    let (new, _) = rest.split_once(" @@")?;
+    Some((parse_range(old)?, parse_range(new)?))
+}
+
+fn parse_patch(patch: &str) -> Result<Vec<PatchHunk>, PatchError> {
+    let mut hunks = Vec::new();
+    let mut lines = patch.split_inclusive('\n').enumerate().peekable();
+    while let Some((number, line)) = lines.next() {
+        if !line.starts_with("@@") {
+            continue; // File headers and anything else between hunks
+        }
+        let malformed = PatchError::Malformed { line: number + 1 };
+        let ((old_start, old_count), (_, new_count)) = parse_header(line).ok_or(malformed)?;
+        let mut hunk = PatchHunk {
+            // An empty range names the line before it
+            old_start: if old_count == 0 {
+                old_start
+            } else {
+                old_start.saturating_sub(1)
+            },
+            old: Vec::new(),
+            new: Vec::new(),
+            leading: 0,
+            trailing: 0,
+        };
+        let mut changed = false;
+        let mut last = number; // Last line read for this hunk
+        while hunk.old.len() < old_count || hunk.new.len() < new_count {
+            let (number, line) = lines
+                .next()
+                .ok_or(PatchError::Malformed { line: last + 2 })?;
+            last = number;
+            let (tag, text) = match line.chars().next() {
+                // Some tools strip the space off empty context lines
+                Some('\n' | '\r') => (' ', line),
+                Some(tag) => (tag, &line[tag.len_utf8()..]),
+                None => (' ', line),
+            };
+            match tag {
+                ' ' => {
+                    hunk.old.push(text.to_string());
+                    hunk.new.push(text.to_string());
+                    if changed {
+                        hunk.trailing += 1;
+                    } else {
+                        hunk.leading += 1;
+                    }
+                }
+                '-' | '+' => {
+                    let side = if tag == '-' {
+                        &mut hunk.old
+                    } else {
+                        &mut hunk.new
+                    };
+                    side.push(text.to_string());
+                    changed = true;
+                    hunk.trailing = 0;
+                }
+                _ => return Err(PatchError::Malformed { line: number + 1 }),
+            }
+            strip_no_newline(&mut lines, &mut hunk, tag);
+        }
+        // A line past the end of one side
+        if hunk.old.len() != old_count || hunk.new.len() != new_count {
+            return Err(PatchError::Malformed { line: last + 1 });
+        }
+        hunks.push(hunk);
+    }
+    Ok(hunks)
+}
+
+// A "\ No newline at end of file" marker takes the break off the line above.
+fn strip_no_newline<'p, I: Iterator<Item = (usize, &'p str)>>(
+    lines: &mut std::iter::Peekable<I>,
+    hunk: &mut PatchHunk,
+    tag: char,
+) {
+    if !lines.peek().is_some_and(|(_, line)| line.starts_with('\\')) {
+        return;
+    }
+    lines.next();
+    let (old, new) = match tag {
+        '-' => (true, false),
+        '+' => (false, true),
+        _ => (true, true),
+    };
+    for (side, strip) in [(&mut hunk.old, old), (&mut hunk.new, new)] {
+        if let Some(last) = side.last_mut().filter(|_| strip) {
+            let trimmed = last.trim_end_matches('\n').trim_end_matches('\r').len();
+            last.truncate(trimmed);
+        }
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Applying
+//   ***************************************************************************
+// Where a hunk goes: lines `start..start + len` become `new`.
+struct Placement<'h> {
+    start: usize,
+    len: usize,
+    new: &'h [String],
+}
+
+fn matches_at(lines: &[RopeSlice<'_>], at: usize, pattern: &[String]) -> bool {
+    at + pattern.len() <= lines.len()
+        && lines[at..at + pattern.len()]
+            .iter()
+            .zip(pattern)
+            .all(|(line, text)| *line == **text)
+}
+
+// Finds the hunk in `lines` at or after `floor`, trying less fuzz before
+// more and smaller offsets before larger ones.
+fn place<'h>(
+    lines: &[RopeSlice<'_>],
+    hunk: &'h PatchHunk,
+    expected: isize,
+    floor: usize,
+    options: &PatchOptions,
+) -> Option<(Placement<'h>, isize, usize)> {
+    for fuzz in 0..=options.max_fuzz {
+        if fuzz > 0 && fuzz > hunk.leading.max(hunk.trailing) {
+            break; // No context left to drop
+        }
+        let head = fuzz.min(hunk.leading);
+        let tail = fuzz.min(hunk.trailing);
+        let old = &hunk.old[head..hunk.old.len() - tail];
+        let new = &hunk.new[head..hunk.new.len() - tail];
+        let expected = expected + head as isize;
+        let lowest = floor as isize;
+        let highest = lines.len() as isize - old.len() as isize;
+        let mut step = 0;
+        while step <= options.max_offset {
+            let (up, down) = (expected + step as isize, expected - step as isize);
+            if up > highest && down < lowest {
+                break; // Both directions have run off the file
+            }
+            for at in [up, down] {
+                if at >= lowest && at <= highest && matches_at(lines, at as usize, old) {
+                    let placement = Placement {
+                        start: at as usize,
+                        len: old.len(),
+                        new,
+                    };
+                    return Some((placement, at - expected, fuzz));
+                }
+            }
+            step += 1;
+        }
+    }
+    None
+}
+
+pub fn apply_patch(rope: &mut Rope, patch: &str) -> Result<PatchReport, PatchError> {
+    apply_patch_with(rope, patch, &PatchOptions::default())
+}
+
+pub fn apply_patch_with(
+    rope: &mut Rope,
+    patch: &str,
+    options: &PatchOptions,
+) -> Result<PatchReport, PatchError> {
+    let hunks = parse_patch(patch)?;
+    let lines = text_lines(rope);
+    let mut report = PatchReport::default();
+    let mut placements = Vec::new();
+    let mut drift = 0; // How far the last hunk landed from its header
+    let mut floor = 0; // Hunks may not overlap or go backwards
+    for hunk in &hunks {
+        let expected = hunk.old_start as isize + drift;
+        match place(&lines, hunk, expected, floor, options) {
+            Some((placement, offset, fuzz)) => {
+                // Measured from where the hunk's first line went, not the
+                // first line fuzz kept
+                let head = fuzz.min(hunk.leading);
+                drift = placement.start as isize - head as isize - hunk.old_start as isize;
+                floor = placement.start + placement.len;
+                report.hunks.push(match (offset, fuzz) {
+                    (0, 0) => HunkOutcome::Applied,
+                    (offset, 0) => HunkOutcome::Offset { offset },
+                    (offset, fuzz) => HunkOutcome::Fuzzed { offset, fuzz },
+                });
+                placements.push(placement);
+            }
+            None => report.hunks.push(HunkOutcome::Rejected),
+        }
+    }
+    if placements.len() < hunks.len() {
+        return Err(PatchError::Rejected(report));
+    }
+
+    // Char ranges are taken before editing; going from the back keeps the
+    // ones still to do valid.
+    let line_count = lines.len();
+    let line_start = |line_idx: usize| {
+        if line_idx >= line_count {
+            rope.len_chars()
+        } else {
+            rope.line_to_char(line_idx)
+        }
+    };
+    let ranges: Vec<(usize, usize)> = placements
+        .iter()
+        .map(|p| (line_start(p.start), line_start(p.start + p.len)))
+        .collect();
+    drop(lines);
+    for (placement, &(start, end)) in placements.iter().zip(&ranges).rev() {
+        rope.remove(start..end);
+        rope.insert_raw(start, &placement.new.concat());
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::{unified_diff, DiffOptions};
+
+    fn numbered(lines: std::ops::Range<usize>) -> String {
+        lines.map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn diff_then_patch_round_trips() {
+        let old = Rope::from(numbered(0..40).as_str());
+        let mut text = numbered(0..40).replace("line 3\n", "three\n");
+        text = text.replace("line 30\n", "line 30\nadded\n");
+        text = text.replace("line 39\n", "");
+        let new = Rope::from(text.as_str());
+        let patch = unified_diff(&old, &new, &DiffOptions::default());
+
+        let mut rope = old.clone();
+        let report = apply_patch(&mut rope, &patch).unwrap();
+        assert_eq!(report.hunks, vec![HunkOutcome::Applied; 3]);
+        assert_eq!(rope.to_string(), text);
+    }
+
+    #[test]
+    fn offset_carries_to_later_hunks() {
+        let old = Rope::from(numbered(0..30).as_str());
+        let new = Rope::from(
+            numbered(0..30)
+                .replace("line 5\n", "five\n")
+                .replace("line 25\n", "")
+                .as_str(),
+        );
+        let patch = unified_diff(&old, &new, &DiffOptions::default());
+
+        // Two lines added at the top push both hunks down
+        let mut rope = Rope::from(format!("x\ny\n{}", numbered(0..30)).as_str());
+        let report = apply_patch(&mut rope, &patch).unwrap();
+        assert_eq!(
+            report.hunks,
+            vec![HunkOutcome::Offset { offset: 2 }, HunkOutcome::Applied]
+        );
+        assert_eq!(rope.to_string(), format!("x\ny\n{}", new));
+    }
+
+    #[test]
+    fn fuzz_on_leading_context_does_not_shift_later_hunks() {
+        let patch = "\
+--- a
++++ b
+@@ -1,4 +1,4 @@
+ line 0
+ line 1
+-line 2
++two
+ line 3
+@@ -11,3 +11,3 @@
+ line 10
+-line 11
++eleven
+ line 12
+";
+        // The first hunk's first context line has changed
+        let mut rope = Rope::from(numbered(0..20).replace("line 0\n", "zero\n").as_str());
+        let report = apply_patch(&mut rope, patch).unwrap();
+        assert_eq!(
+            report.hunks,
+            vec![
+                HunkOutcome::Fuzzed { offset: 0, fuzz: 1 },
+                HunkOutcome::Applied
+            ]
+        );
+        let expected = numbered(0..20)
+            .replace("line 0\n", "zero\n")
+            .replace("line 2\n", "two\n")
+            .replace("line 11\n", "eleven\n");
+        assert_eq!(rope.to_string(), expected);
+    }
+
+    #[test]
+    fn a_rejected_hunk_leaves_the_rope_alone() {
+        let patch = "\
+--- a
++++ b
+@@ -1,2 +1,2 @@
+ line 0
+-line 1
++one
+@@ -6,2 +6,2 @@
+ line 5
+-missing
++six
+";
+        let mut rope = Rope::from(numbered(0..10).as_str());
+        let err = apply_patch(&mut rope, patch).unwrap_err();
+        assert_eq!(
+            err,
+            PatchError::Rejected(PatchReport {
+                hunks: vec![HunkOutcome::Applied, HunkOutcome::Rejected]
+            })
+        );
+        assert_eq!(err.to_string(), "1 of 2 hunks rejected");
+        assert_eq!(rope.to_string(), numbered(0..10));
+    }
+
+    #[test]
+    fn malformed_patches_name_the_line() {
+        let mut rope = Rope::from("a\n");
+        let patch = "--- a\n+++ b\n@@ -1,2 +1,1 @@\n a\n";
+        assert_eq!(
+            apply_patch(&mut rope, patch),
+            Err(PatchError::Malformed { line: 5 })
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//NOTE: ---------------------- RED-BLACK CORE -----------------------------------
+//
+// The balancing steps shared by RedBlackTree and Rope. A node only has to
+// expose its color and children and recompute its aggregates (sizes,
+// weights) from its children; each tree keeps its own rules for when to
+// rotate or recolor. Trees that cache their black height can also be joined
+// in time proportional to the difference in height.
+
+use crate::Color;
+use std::cmp::Ordering;
+
+pub(crate) trait RbNode: Sized {
+    fn color(&self) -> Color;
+    fn set_color(&mut self, color: Color);
+    fn left(&self) -> &Option<Box<Self>>;
+    fn right(&self) -> &Option<Box<Self>>;
+    fn left_mut(&mut self) -> &mut Option<Box<Self>>;
+    fn right_mut(&mut self) -> &mut Option<Box<Self>>;
+    // Recomputes the aggregates from the children.
+    fn update(&mut self);
+}
+
+pub(crate) trait JoinNode: RbNode {
+    fn black_height(&self) -> usize;
+    // Makes the node black, one level taller if it was red.
+    fn blacken(&mut self);
+}
+
+pub(crate) fn is_red<N: RbNode>(node: &Option<Box<N>>) -> bool {
+    node.as_ref().is_some_and(|n| n.color().is_red())
+}
+
+//NOTE:***************************************************************************
+//   *  Rotations and color flips
+//   ***************************************************************************
+// Lifts the right child into `x`'s place. Colors are left to the caller; the
+// aggregates are redone bottom-up, `x` first and then its new parent.
+pub(crate) fn rotate_left<N: RbNode>(mut x: Box<N>) -> Box<N> {
+    let mut y = x
+        .right_mut()
+        .take()
+        .expect("rotate_left needs a right child");
+    *x.right_mut() = y.left_mut().take();
+    x.update();
+    *y.left_mut() = Some(x);
+    y.update();
+    y
+}
+
+pub(crate) fn rotate_right<N: RbNode>(mut x: Box<N>) -> Box<N> {
+    let mut y = x
+        .left_mut()
+        .take()
+        .expect("rotate_right needs a left child");
+    *x.left_mut() = y.right_mut().take();
+    x.update();
+    *y.right_mut() = Some(x);
+    y.update();
+    y
+}
+
+// Recolors a node and both of its children.
+pub(crate) fn flip_colors<N: RbNode>(n: &mut N) {
+    n.set_color(n.color().flip_color());
+    if let Some(left) = n.left_mut() {
+        left.set_color(left.color().flip_color());
+    }
+    if let Some(right) = n.right_mut() {
+        right.set_color(right.color().flip_color());
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Join
+//   ***************************************************************************
+// Joins `a` and `b` with `mid`, a node without children, between them. The
+// result has a black root.
+pub(crate) fn join<N: JoinNode>(mut a: Box<N>, mid: Box<N>, mut b: Box<N>) -> Box<N> {
+    a.blacken();
+    b.blacken();
+    let mut joined = match a.black_height().cmp(&b.black_height()) {
+        Ordering::Equal => attach(mid, Color::Black, a, b),
+        Ordering::Greater => join_right(a, mid, b),
+        Ordering::Less => join_left(a, mid, b),
+    };
+    joined.blacken();
+    joined
+}
+
+fn attach<N: RbNode>(mut mid: Box<N>, color: Color, a: Box<N>, b: Box<N>) -> Box<N> {
+    mid.set_color(color);
+    *mid.left_mut() = Some(a);
+    *mid.right_mut() = Some(b);
+    mid.update();
+    mid
+}
+
+// Hangs `b` off the right spine of `a` at the first black node of equal black
+// height, then repairs red-red edges on the way back up.
+fn join_right<N: JoinNode>(mut a: Box<N>, mid: Box<N>, b: Box<N>) -> Box<N> {
+    if !a.color().is_red() && a.black_height() == b.black_height() {
+        return attach(mid, Color::Red, a, b);
+    }
+    let spine = a.right_mut().take().expect("the taller tree has children");
+    *a.right_mut() = Some(join_right(spine, mid, b));
+    a.update();
+
+    let black = !a.color().is_red();
+    let right = a.right_mut().as_mut().unwrap();
+    if black && right.color().is_red() && is_red(right.right()) {
+        right.right_mut().as_mut().unwrap().blacken();
+        a = rotate_left(a);
+    }
+    a
+}
+
+fn join_left<N: JoinNode>(a: Box<N>, mid: Box<N>, mut b: Box<N>) -> Box<N> {
+    if !b.color().is_red() && a.black_height() == b.black_height() {
+        return attach(mid, Color::Red, a, b);
+    }
+    let spine = b.left_mut().take().expect("the taller tree has children");
+    *b.left_mut() = Some(join_left(a, mid, spine));
+    b.update();
+
+    let black = !b.color().is_red();
+    let left = b.left_mut().as_mut().unwrap();
+    if black && left.color().is_red() && is_red(left.left()) {
+        left.left_mut().as_mut().unwrap().blacken();
+        b = rotate_right(b);
+    }
+    b
+}
@@ -0,0 +1,1086 @@
+//NOTE: ---------------------- ROPE IMPLEMENTATION -----------------------------
+//
+// A rope is a red-black tree whose leaves hold text chunks and whose internal
+// nodes only carry the weights (bytes, chars) of everything below them.
+// Balancing is join-based: `join` glues two trees by black height and `split`
+// cuts one at a char index, so insert/remove/concat are all O(log n). The
+// rotations and the join itself come from the crate's red-black core.
+
+use crate::anchor::AnchorSet;
+use crate::file::FileFormat;
+use crate::line_ending::LineEnding;
+use crate::redblack::{self, JoinNode, RbNode};
+use crate::slice::RopeSlice;
+use crate::Color;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Bound, Range, RangeBounds};
+
+pub(crate) const MAX_CHUNK_BYTES: usize = 1024;
+pub(crate) const MIN_CHUNK_BYTES: usize = MAX_CHUNK_BYTES / 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeError {
+    Reversed { start: usize, end: usize },
+    OutOfBounds { index: usize, len: usize },
+    NotCharBoundary { index: usize },
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::Reversed { start, end } => {
+                write!(f, "range start {} is greater than end {}", start, end)
+            }
+            RangeError::OutOfBounds { index, len } => {
+                write!(f, "index {} out of range for length {}", index, len)
+            }
+            RangeError::NotCharBoundary { index } => {
+                write!(f, "byte index {} is not a char boundary", index)
+            }
+        }
+    }
+}
+
+impl Error for RangeError {}
+
+// Turns any `RangeBounds` into a checked `start..end` within `0..=len`.
+pub(crate) fn resolve_range<R: RangeBounds<usize>>(
+    range: R,
+    len: usize,
+) -> Result<Range<usize>, RangeError> {
+    let start = match range.start_bound() {
+        Bound::Included(&s) => s,
+        Bound::Excluded(&s) => s.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&e) => e.saturating_add(1),
+        Bound::Excluded(&e) => e,
+        Bound::Unbounded => len,
+    };
+    if start > end {
+        return Err(RangeError::Reversed { start, end });
+    }
+    if end > len {
+        return Err(RangeError::OutOfBounds { index: end, len });
+    }
+    Ok(start..end)
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct TextInfo {
+    pub(crate) bytes: usize,
+    pub(crate) chars: usize,
+    pub(crate) utf16: usize, // UTF-16 code units, for LSP positions
+    // `\n`, `\r\n` and lone `\r` each count once. A `\r\n` cut across two
+    // chunks is seen as `\r` + `\n` by the halves, so the edge flags let
+    // `combine` drop the duplicate.
+    pub(crate) line_breaks: usize,
+    pub(crate) cr: usize,   // Every `\r`, paired or not
+    pub(crate) crlf: usize, // `\r\n` pairs, including ones cut across chunks
+    pub(crate) starts_with_lf: bool,
+    pub(crate) ends_with_cr: bool,
+}
+
+impl TextInfo {
+    pub(crate) fn of(text: &str) -> TextInfo {
+        let mut info = TextInfo {
+            bytes: text.len(),
+            starts_with_lf: text.starts_with('\n'),
+            ends_with_cr: text.ends_with('\r'),
+            ..TextInfo::default()
+        };
+        let mut prev_cr = false;
+        for c in text.chars() {
+            info.chars += 1;
+            info.utf16 += c.len_utf16();
+            if c == '\r' || (c == '\n' && !prev_cr) {
+                info.line_breaks += 1;
+            }
+            info.cr += (c == '\r') as usize;
+            info.crlf += (c == '\n' && prev_cr) as usize;
+            prev_cr = c == '\r';
+        }
+        info
+    }
+
+    pub(crate) fn combine(&self, right: &TextInfo) -> TextInfo {
+        let crlf_seam = self.ends_with_cr && right.starts_with_lf;
+        TextInfo {
+            bytes: self.bytes + right.bytes,
+            chars: self.chars + right.chars,
+            utf16: self.utf16 + right.utf16,
+            line_breaks: self.line_breaks + right.line_breaks - crlf_seam as usize,
+            cr: self.cr + right.cr,
+            crlf: self.crlf + right.crlf + crlf_seam as usize,
+            starts_with_lf: if self.bytes == 0 {
+                right.starts_with_lf
+            } else {
+                self.starts_with_lf
+            },
+            ends_with_cr: if right.bytes == 0 {
+                self.ends_with_cr
+            } else {
+                right.ends_with_cr
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RopeNode {
+    pub(crate) chunk: String, // Only leaves carry text
+    pub(crate) left: Option<Box<RopeNode>>,
+    pub(crate) right: Option<Box<RopeNode>>,
+    color: Color,
+    black_height: usize,
+    pub(crate) leaves: usize,
+    pub(crate) info: TextInfo, // Weights of the whole subtree
+}
+
+impl RopeNode {
+    fn leaf(chunk: String) -> Box<RopeNode> {
+        let info = TextInfo::of(&chunk);
+        Box::new(RopeNode {
+            chunk,
+            left: None,
+            right: None,
+            color: Color::Black,
+            black_height: 1,
+            leaves: 1,
+            info,
+        })
+    }
+
+    // An internal node to join two trees with; `join` fills in its children.
+    fn joint() -> Box<RopeNode> {
+        Box::new(RopeNode {
+            chunk: String::new(),
+            left: None,
+            right: None,
+            color: Color::Black,
+            black_height: 0,
+            leaves: 0,
+            info: TextInfo::default(),
+        })
+    }
+
+    pub(crate) fn is_leaf(&self) -> bool {
+        self.left.is_none()
+    }
+
+    fn is_red(&self) -> bool {
+        self.color.is_red()
+    }
+}
+
+impl RbNode for RopeNode {
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    fn left(&self) -> &Option<Box<RopeNode>> {
+        &self.left
+    }
+
+    fn right(&self) -> &Option<Box<RopeNode>> {
+        &self.right
+    }
+
+    fn left_mut(&mut self) -> &mut Option<Box<RopeNode>> {
+        &mut self.left
+    }
+
+    fn right_mut(&mut self) -> &mut Option<Box<RopeNode>> {
+        &mut self.right
+    }
+
+    // Recomputes the weights and black height from the children.
+    fn update(&mut self) {
+        if let (Some(l), Some(r)) = (&self.left, &self.right) {
+            self.info = l.info.combine(&r.info);
+            self.black_height = l.black_height + if self.is_red() { 0 } else { 1 };
+            self.leaves = l.leaves + r.leaves;
+        } else {
+            self.info = TextInfo::of(&self.chunk);
+            self.black_height = 1;
+            self.leaves = 1;
+        }
+    }
+}
+
+impl JoinNode for RopeNode {
+    fn black_height(&self) -> usize {
+        self.black_height
+    }
+
+    fn blacken(&mut self) {
+        if self.is_red() {
+            self.color = self.color.flip_color();
+            self.black_height += 1;
+        }
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Join and split
+//   ***************************************************************************
+// Concatenates two trees. Both may be any valid red-black subtree; the result
+// has a black root.
+pub(crate) fn join(
+    left: Option<Box<RopeNode>>,
+    right: Option<Box<RopeNode>>,
+) -> Option<Box<RopeNode>> {
+    match (left, right) {
+        (None, only) | (only, None) => only.map(|mut n| {
+            n.blacken();
+            n
+        }),
+        (Some(a), Some(b)) => Some(redblack::join(a, RopeNode::joint(), b)),
+    }
+}
+
+// Cuts the tree so that the left part holds exactly `char_idx` chars.
+pub(crate) fn split(
+    node: Option<Box<RopeNode>>,
+    char_idx: usize,
+) -> (Option<Box<RopeNode>>, Option<Box<RopeNode>>) {
+    let mut n = match node {
+        Some(n) => n,
+        None => return (None, None),
+    };
+    if char_idx == 0 {
+        return (None, Some(n));
+    }
+    if char_idx >= n.info.chars {
+        return (Some(n), None);
+    }
+
+    if n.is_leaf() {
+        let at = char_to_byte_in(&n.chunk, char_idx);
+        let tail = n.chunk.split_off(at);
+        n.update();
+        return (Some(n), Some(RopeNode::leaf(tail)));
+    }
+
+    let left = n.left.take();
+    let right = n.right.take();
+    let left_chars = left.as_ref().map_or(0, |l| l.info.chars);
+    if char_idx <= left_chars {
+        let (a, b) = split(left, char_idx);
+        (a, join(b, right))
+    } else {
+        let (a, b) = split(right, char_idx - left_chars);
+        (join(left, a), b)
+    }
+}
+
+// Builds a balanced tree out of `text`, cut into chunks of at most
+// MAX_CHUNK_BYTES on char boundaries.
+pub(crate) fn build(text: &str) -> Option<Box<RopeNode>> {
+    let chunks: Vec<String> = chunks_of(text).into_iter().map(String::from).collect();
+    build_from_chunks(chunks)
+}
+
+// One leaf per chunk, in order.
+pub(crate) fn build_from_chunks(mut chunks: Vec<String>) -> Option<Box<RopeNode>> {
+    match chunks.len() {
+        0 => None,
+        1 => chunks.pop().map(RopeNode::leaf),
+        n => {
+            let right = chunks.split_off(n / 2);
+            join(build_from_chunks(chunks), build_from_chunks(right))
+        }
+    }
+}
+
+pub(crate) fn chunks_of(text: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while rest.len() > MAX_CHUNK_BYTES {
+        let mut at = MAX_CHUNK_BYTES;
+        while !rest.is_char_boundary(at) {
+            at -= 1;
+        }
+        let (head, tail) = rest.split_at(at);
+        chunks.push(head);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+fn first_leaf(node: &Option<Box<RopeNode>>) -> Option<&RopeNode> {
+    let mut n = node.as_deref()?;
+    while let Some(l) = n.left.as_deref() {
+        n = l;
+    }
+    Some(n)
+}
+
+fn last_leaf(node: &Option<Box<RopeNode>>) -> Option<&RopeNode> {
+    let mut n = node.as_deref()?;
+    while let Some(r) = n.right.as_deref() {
+        n = r;
+    }
+    Some(n)
+}
+
+// Joins two trees, merging the leaves on either side of the seam when one of
+// them has become too small, so repeated edits don't fragment the rope.
+pub(crate) fn join_at_seam(
+    left: Option<Box<RopeNode>>,
+    right: Option<Box<RopeNode>>,
+) -> Option<Box<RopeNode>> {
+    let (tail, head) = match (last_leaf(&left), first_leaf(&right)) {
+        (Some(t), Some(h)) => (t.info, h.info),
+        _ => return join(left, right),
+    };
+    let small = tail.bytes < MIN_CHUNK_BYTES || head.bytes < MIN_CHUNK_BYTES;
+    if !small || tail.bytes + head.bytes > MAX_CHUNK_BYTES {
+        return join(left, right);
+    }
+
+    let left_chars = left.as_ref().unwrap().info.chars;
+    let (left, tail_leaf) = split(left, left_chars - tail.chars);
+    let (head_leaf, right) = split(right, head.chars);
+    let mut merged = tail_leaf.unwrap().chunk;
+    merged.push_str(&head_leaf.unwrap().chunk);
+    join(join(left, Some(RopeNode::leaf(merged))), right)
+}
+
+pub(crate) fn char_to_byte_in(text: &str, char_idx: usize) -> usize {
+    text.char_indices()
+        .nth(char_idx)
+        .map_or(text.len(), |(byte, _)| byte)
+}
+
+//NOTE:***************************************************************************
+//   *  Rope
+//   ***************************************************************************
+#[derive(Clone, Default)]
+pub struct Rope {
+    pub(crate) root: Option<Box<RopeNode>>,
+    pub(crate) anchors: AnchorSet,
+    pub(crate) format: FileFormat, // What `load` found, and what a save writes
+    pub(crate) insert_line_ending: Option<LineEnding>,
+}
+
+impl Rope {
+    pub fn new() -> Self {
+        Rope {
+            root: None,
+            anchors: AnchorSet::default(),
+            format: FileFormat::default(),
+            insert_line_ending: None,
+        }
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.root.as_ref().map_or(0, |n| n.info.bytes)
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.root.as_ref().map_or(0, |n| n.info.chars)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    // Appends `other` to the end of this rope. Anchors of `other` are dropped.
+    pub fn concat(&mut self, other: Rope) {
+        self.anchors.insert(self.len_chars(), other.len_chars());
+        self.root = join_at_seam(self.root.take(), other.root);
+    }
+
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        let text = self.converted(text);
+        self.insert_raw(char_idx, &text);
+    }
+
+    // Inserts `text` as given, whatever `set_insert_line_ending` says.
+    pub(crate) fn insert_raw(&mut self, char_idx: usize, text: &str) {
+        if char_idx > self.len_chars() {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: char_idx,
+                    len: self.len_chars()
+                }
+            );
+        }
+        if text.is_empty() {
+            return;
+        }
+        self.anchors.insert(char_idx, text.chars().count());
+        if let Some(ref mut root) = self.root {
+            if insert_in_leaf(root, char_idx, text) {
+                return;
+            }
+        }
+        let (left, right) = split(self.root.take(), char_idx);
+        self.root = join_at_seam(join_at_seam(left, build(text)), right);
+    }
+
+    pub fn remove<R: RangeBounds<usize>>(&mut self, range: R) {
+        let Range { start, end } = self.char_range(range);
+        if start == end {
+            return;
+        }
+        self.anchors.remove(start, end);
+        if let Some(ref mut root) = self.root {
+            if remove_in_leaf(root, start, end) {
+                return;
+            }
+        }
+        let (left, rest) = split(self.root.take(), start);
+        let (_, right) = split(rest, end - start);
+        self.root = join_at_seam(left, right);
+    }
+
+    // A borrowed view of the chars in `range`. Panics where `try_slice` would
+    // return an error.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> RopeSlice<'_> {
+        self.try_slice(range).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_slice<R: RangeBounds<usize>>(&self, range: R) -> Result<RopeSlice<'_>, RangeError> {
+        let Range { start, end } = resolve_range(range, self.len_chars())?;
+        Ok(RopeSlice::new(self, start, end))
+    }
+
+    pub fn substring<R: RangeBounds<usize>>(&self, range: R) -> Result<String, RangeError> {
+        let Range { start, end } = resolve_range(range, self.len_chars())?;
+        let mut out = String::new();
+        collect(&self.root, start, end, &mut out);
+        Ok(out)
+    }
+
+    // Same as `substring` but with byte offsets, which must be char boundaries.
+    pub fn byte_substring<R: RangeBounds<usize>>(&self, range: R) -> Result<String, RangeError> {
+        let Range { start, end } = resolve_range(range, self.len_bytes())?;
+        let start = self.try_byte_to_char(start)?;
+        let end = self.try_byte_to_char(end)?;
+        self.substring(start..end)
+    }
+
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        if char_idx > self.len_chars() {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: char_idx,
+                    len: self.len_chars()
+                }
+            );
+        }
+        let mut node = self.root.as_deref();
+        let mut chars = char_idx;
+        let mut bytes = 0;
+        while let Some(n) = node {
+            if n.is_leaf() {
+                return bytes + char_to_byte_in(&n.chunk, chars);
+            }
+            let left = n.left.as_ref().unwrap();
+            if chars <= left.info.chars {
+                node = n.left.as_deref();
+            } else {
+                chars -= left.info.chars;
+                bytes += left.info.bytes;
+                node = n.right.as_deref();
+            }
+        }
+        bytes
+    }
+
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        self.try_byte_to_char(byte_idx)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn is_char_boundary(&self, byte_idx: usize) -> bool {
+        self.try_byte_to_char(byte_idx).is_ok()
+    }
+
+    fn try_byte_to_char(&self, byte_idx: usize) -> Result<usize, RangeError> {
+        if byte_idx > self.len_bytes() {
+            return Err(RangeError::OutOfBounds {
+                index: byte_idx,
+                len: self.len_bytes(),
+            });
+        }
+        let mut node = self.root.as_deref();
+        let mut bytes = byte_idx;
+        let mut chars = 0;
+        while let Some(n) = node {
+            if n.is_leaf() {
+                if !n.chunk.is_char_boundary(bytes) {
+                    return Err(RangeError::NotCharBoundary { index: byte_idx });
+                }
+                return Ok(chars + n.chunk[..bytes].chars().count());
+            }
+            let left = n.left.as_ref().unwrap();
+            if bytes <= left.info.bytes {
+                node = n.left.as_deref();
+            } else {
+                bytes -= left.info.bytes;
+                chars += left.info.chars;
+                node = n.right.as_deref();
+            }
+        }
+        Ok(chars)
+    }
+
+    pub(crate) fn char_range<R: RangeBounds<usize>>(&self, range: R) -> Range<usize> {
+        resolve_range(range, self.len_chars()).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub(crate) fn char_at(&self, char_idx: usize) -> Option<char> {
+        let mut node = self.root.as_deref()?;
+        let mut idx = char_idx;
+        while !node.is_leaf() {
+            let left = node.left.as_deref().unwrap();
+            if idx < left.info.chars {
+                node = left;
+            } else {
+                idx -= left.info.chars;
+                node = node.right.as_deref().unwrap();
+            }
+        }
+        node.chunk.chars().nth(idx)
+    }
+
+    // Weights of the first `char_idx` chars, as if they were their own rope.
+    pub(crate) fn prefix_info(&self, char_idx: usize) -> TextInfo {
+        let mut acc = TextInfo::default();
+        let mut node = self.root.as_deref();
+        let mut idx = char_idx.min(self.len_chars());
+        while let Some(n) = node {
+            if n.is_leaf() {
+                let at = char_to_byte_in(&n.chunk, idx);
+                return acc.combine(&TextInfo::of(&n.chunk[..at]));
+            }
+            let left = n.left.as_deref().unwrap();
+            if idx <= left.info.chars {
+                node = Some(left);
+            } else {
+                idx -= left.info.chars;
+                acc = acc.combine(&left.info);
+                node = n.right.as_deref();
+            }
+        }
+        acc
+    }
+
+    // True when `char_idx` sits between the `\r` and `\n` of a CRLF.
+    pub(crate) fn inside_crlf(&self, char_idx: usize) -> bool {
+        char_idx > 0
+            && self.char_at(char_idx) == Some('\n')
+            && self.char_at(char_idx - 1) == Some('\r')
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  UTF-16 offsets
+//   ***************************************************************************
+impl Rope {
+    pub fn len_utf16(&self) -> usize {
+        self.root.as_ref().map_or(0, |n| n.info.utf16)
+    }
+
+    pub fn char_to_utf16(&self, char_idx: usize) -> usize {
+        if char_idx > self.len_chars() {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: char_idx,
+                    len: self.len_chars()
+                }
+            );
+        }
+        self.prefix_info(char_idx).utf16
+    }
+
+    // An offset that points at the low half of a surrogate pair is rounded
+    // down to the char the pair encodes.
+    pub fn utf16_to_char(&self, utf16_idx: usize) -> usize {
+        if utf16_idx > self.len_utf16() {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: utf16_idx,
+                    len: self.len_utf16()
+                }
+            );
+        }
+        let mut node = self.root.as_deref();
+        let mut units = utf16_idx;
+        let mut chars = 0;
+        while let Some(n) = node {
+            if n.is_leaf() {
+                for c in n.chunk.chars() {
+                    if units < c.len_utf16() {
+                        break;
+                    }
+                    units -= c.len_utf16();
+                    chars += 1;
+                }
+                break;
+            }
+            let left = n.left.as_deref().unwrap();
+            if units <= left.info.utf16 {
+                node = Some(left);
+            } else {
+                units -= left.info.utf16;
+                chars += left.info.chars;
+                node = n.right.as_deref();
+            }
+        }
+        chars
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Line indexing
+//   ***************************************************************************
+impl Rope {
+    pub fn len_lines(&self) -> usize {
+        self.root.as_ref().map_or(0, |n| n.info.line_breaks) + 1
+    }
+
+    // Index of the line holding `char_idx`; the chars of a line break belong to
+    // the line they end.
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        if char_idx > self.len_chars() {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: char_idx,
+                    len: self.len_chars()
+                }
+            );
+        }
+        let breaks = self.prefix_info(char_idx).line_breaks;
+        if self.inside_crlf(char_idx) {
+            breaks - 1
+        } else {
+            breaks
+        }
+    }
+
+    // Char index where `line_idx` starts; `len_lines()` maps to the end.
+    pub fn line_to_char(&self, line_idx: usize) -> usize {
+        if line_idx > self.len_lines() {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: line_idx,
+                    len: self.len_lines()
+                }
+            );
+        }
+        if line_idx == 0 {
+            return 0;
+        }
+        if line_idx == self.len_lines() {
+            return self.len_chars();
+        }
+
+        // Find the char that completes break number `line_idx`.
+        let mut acc = TextInfo::default();
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            if n.is_leaf() {
+                break;
+            }
+            let left = n.left.as_deref().unwrap();
+            let with_left = acc.combine(&left.info);
+            if with_left.line_breaks >= line_idx {
+                node = Some(left);
+            } else {
+                acc = with_left;
+                node = n.right.as_deref();
+            }
+        }
+
+        let leaf = node.unwrap();
+        let mut breaks = acc.line_breaks;
+        let mut prev_cr = acc.ends_with_cr;
+        let mut pos = acc.chars;
+        for c in leaf.chunk.chars() {
+            pos += 1;
+            if c == '\r' || (c == '\n' && !prev_cr) {
+                breaks += 1;
+            }
+            prev_cr = c == '\r';
+            if breaks == line_idx {
+                break;
+            }
+        }
+        // A `\r` only ends the line once its `\n`, if any, is included.
+        if self.inside_crlf(pos) {
+            pos + 1
+        } else {
+            pos
+        }
+    }
+
+    pub fn line_to_byte(&self, line_idx: usize) -> usize {
+        self.char_to_byte(self.line_to_char(line_idx))
+    }
+
+    pub fn byte_to_line(&self, byte_idx: usize) -> usize {
+        self.char_to_line(self.byte_to_char(byte_idx))
+    }
+
+    // The text of line `line_idx`, including its line break.
+    pub fn line(&self, line_idx: usize) -> RopeSlice<'_> {
+        if line_idx >= self.len_lines() {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: line_idx,
+                    len: self.len_lines()
+                }
+            );
+        }
+        self.slice(self.line_to_char(line_idx)..self.line_to_char(line_idx + 1))
+    }
+}
+
+// Edits the leaf holding `char_idx` in place when the text fits, updating the
+// weights on the way back up. Returns false when the tree has to be rebuilt.
+fn insert_in_leaf(node: &mut RopeNode, char_idx: usize, text: &str) -> bool {
+    if node.is_leaf() {
+        if node.chunk.len() + text.len() > MAX_CHUNK_BYTES {
+            return false;
+        }
+        let at = char_to_byte_in(&node.chunk, char_idx);
+        node.chunk.insert_str(at, text);
+        node.update();
+        return true;
+    }
+    let left_chars = node.left.as_ref().unwrap().info.chars;
+    let done = if char_idx <= left_chars {
+        insert_in_leaf(node.left.as_mut().unwrap(), char_idx, text)
+    } else {
+        insert_in_leaf(node.right.as_mut().unwrap(), char_idx - left_chars, text)
+    };
+    if done {
+        node.update();
+    }
+    done
+}
+
+fn remove_in_leaf(node: &mut RopeNode, start: usize, end: usize) -> bool {
+    if node.is_leaf() {
+        if end - start >= node.info.chars {
+            return false; // Would leave an empty leaf behind
+        }
+        let from = char_to_byte_in(&node.chunk, start);
+        let to = char_to_byte_in(&node.chunk, end);
+        node.chunk.replace_range(from..to, "");
+        node.update();
+        return true;
+    }
+    let left_chars = node.left.as_ref().unwrap().info.chars;
+    let done = if end <= left_chars {
+        remove_in_leaf(node.left.as_mut().unwrap(), start, end)
+    } else if start >= left_chars {
+        remove_in_leaf(
+            node.right.as_mut().unwrap(),
+            start - left_chars,
+            end - left_chars,
+        )
+    } else {
+        false
+    };
+    if done {
+        node.update();
+    }
+    done
+}
+
+fn collect(node: &Option<Box<RopeNode>>, start: usize, end: usize, out: &mut String) {
+    if let Some(n) = node {
+        if start >= end {
+            return;
+        }
+        if n.is_leaf() {
+            let from = char_to_byte_in(&n.chunk, start);
+            let to = char_to_byte_in(&n.chunk, end);
+            out.push_str(&n.chunk[from..to]);
+            return;
+        }
+        let left_chars = n.left.as_ref().unwrap().info.chars;
+        if start < left_chars {
+            collect(&n.left, start, end.min(left_chars), out);
+        }
+        if end > left_chars {
+            collect(
+                &n.right,
+                start.saturating_sub(left_chars),
+                end - left_chars,
+                out,
+            );
+        }
+    }
+}
+
+fn write_chunks(node: &Option<Box<RopeNode>>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(n) = node {
+        if n.is_leaf() {
+            return f.write_str(&n.chunk);
+        }
+        write_chunks(&n.left, f)?;
+        write_chunks(&n.right, f)?;
+    }
+    Ok(())
+}
+
+impl From<&str> for Rope {
+    fn from(text: &str) -> Self {
+        Rope {
+            root: build(text),
+            ..Rope::new()
+        }
+    }
+}
+
+impl From<String> for Rope {
+    fn from(text: String) -> Self {
+        Rope::from(text.as_str())
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_chunks(&self.root, f)
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Rope").field(&self.to_string()).finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tests::Rng;
+
+    // Returns the black height after checking colors, weights and chunk sizes.
+    fn check_node(node: &RopeNode, parent_red: bool) -> usize {
+        assert!(!(parent_red && node.is_red()), "two red nodes in a row");
+        if node.is_leaf() {
+            assert!(node.right.is_none(), "leaf with a right child");
+            assert!(!node.chunk.is_empty(), "empty leaf");
+            assert!(node.chunk.len() <= MAX_CHUNK_BYTES, "oversized leaf");
+            assert_eq!(node.info, TextInfo::of(&node.chunk));
+            assert_eq!((node.leaves, node.black_height), (1, 1));
+            return 1;
+        }
+        assert!(node.chunk.is_empty(), "internal node with text");
+        let (l, r) = (node.left.as_ref().unwrap(), node.right.as_ref().unwrap());
+        let left = check_node(l, node.is_red());
+        let right = check_node(r, node.is_red());
+        assert_eq!(left, right, "black heights differ");
+        let height = left + !node.is_red() as usize;
+        assert_eq!(node.black_height, height);
+        assert_eq!(node.info, l.info.combine(&r.info));
+        assert_eq!(node.leaves, l.leaves + r.leaves);
+        height
+    }
+
+    fn depth(node: &Option<Box<RopeNode>>) -> usize {
+        match node {
+            Some(n) => 1 + depth(&n.left).max(depth(&n.right)),
+            None => 0,
+        }
+    }
+
+    // Panics unless the rope is a valid, balanced red-black tree.
+    pub(crate) fn check(rope: &Rope) {
+        if let Some(root) = &rope.root {
+            assert!(!root.is_red(), "red root");
+            check_node(root, false);
+            let bound = 2.0 * (root.leaves as f64 + 1.0).log2() + 1.0;
+            assert!(depth(&rope.root) as f64 <= bound, "tree too deep");
+        }
+    }
+
+    pub(crate) fn byte_of(text: &str, char_idx: usize) -> usize {
+        char_to_byte_in(text, char_idx)
+    }
+
+    // A rope with exactly these leaves, to put seams where a test needs them.
+    pub(crate) fn from_chunks(chunks: &[&str]) -> Rope {
+        let mut rope = Rope::new();
+        rope.root = build_from_chunks(chunks.iter().map(|c| c.to_string()).collect());
+        rope
+    }
+
+    #[test]
+    fn edits_match_a_string() {
+        let pieces = ["a", "héllo ", "wörld\n", "日本語", "🦀🦀", "\r\n", "xyz"];
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let mut rope = Rope::new();
+        let mut model = String::new();
+        for step in 0..3000 {
+            let len = model.chars().count();
+            if len > 0 && rng.below(3) == 0 {
+                let start = rng.below(len);
+                let end = start + rng.below((len - start).min(300) + 1);
+                rope.remove(start..end);
+                model.replace_range(byte_of(&model, start)..byte_of(&model, end), "");
+            } else {
+                let at = rng.below(len + 1);
+                let text = pieces[rng.below(pieces.len())].repeat(1 + rng.below(40));
+                rope.insert(at, &text);
+                model.insert_str(byte_of(&model, at), &text);
+            }
+            if step % 50 == 0 {
+                check(&rope);
+            }
+            assert_eq!(rope.len_chars(), model.chars().count());
+            assert_eq!(rope.len_bytes(), model.len());
+        }
+        check(&rope);
+        assert_eq!(rope.to_string(), model);
+    }
+
+    #[test]
+    fn concat_and_slice() {
+        let mut rope = Rope::from("Hello");
+        rope.concat(Rope::from(" World"));
+        rope.insert(5, ",");
+        check(&rope);
+        assert_eq!(rope.to_string(), "Hello, World");
+        assert_eq!(rope.slice(0..5).to_string(), "Hello");
+
+        let long = "ab".repeat(3000);
+        let mut rope = Rope::from(long.as_str());
+        for _ in 0..10 {
+            rope.concat(Rope::from(long.as_str()));
+        }
+        check(&rope);
+        assert_eq!(rope.len_chars(), 11 * 6000);
+        assert_eq!(rope.slice(5999..6001).to_string(), "ba");
+    }
+
+    #[test]
+    fn remove_everything() {
+        let mut rope = Rope::from("x".repeat(5000).as_str());
+        rope.remove(..);
+        assert!(rope.is_empty());
+        assert_eq!(rope.to_string(), "");
+    }
+
+    #[test]
+    fn substring_counts_chars_across_leaves() {
+        let rope = from_chunks(&["h\u{e9}l", "lo \u{1f980}", "\u{65e5}\u{672c}"]);
+        check(&rope);
+        assert_eq!(rope.len_chars(), 9);
+        assert_eq!(rope.substring(1..7), Ok("\u{e9}llo \u{1f980}".to_string()));
+        assert_eq!(rope.substring(..), Ok(rope.to_string()));
+        assert_eq!(rope.substring(9..), Ok(String::new()));
+        let (start, end) = (4, 2);
+        assert_eq!(
+            rope.substring(start..end),
+            Err(RangeError::Reversed { start: 4, end: 2 })
+        );
+        assert_eq!(
+            rope.substring(0..10),
+            Err(RangeError::OutOfBounds { index: 10, len: 9 })
+        );
+    }
+
+    #[test]
+    fn byte_offsets_must_be_char_boundaries() {
+        let rope = from_chunks(&["a\u{e9}", "\u{1f980}b"]);
+        assert_eq!(rope.len_bytes(), 8);
+        assert_eq!(rope.char_to_byte(2), 3);
+        assert_eq!(rope.byte_to_char(7), 3);
+        assert!(rope.is_char_boundary(3));
+        assert!(!rope.is_char_boundary(2));
+        assert!(!rope.is_char_boundary(5));
+        assert_eq!(rope.byte_substring(1..7), Ok("\u{e9}\u{1f980}".to_string()));
+        assert_eq!(
+            rope.byte_substring(0..5),
+            Err(RangeError::NotCharBoundary { index: 5 })
+        );
+        for c in 0..=rope.len_chars() {
+            assert_eq!(rope.byte_to_char(rope.char_to_byte(c)), c);
+        }
+    }
+
+    // Char index where each line starts, counting `\r\n`, `\r` and `\n` as
+    // one break each.
+    fn line_starts(text: &str) -> Vec<usize> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut starts = vec![0];
+        for (i, &c) in chars.iter().enumerate() {
+            if c == '\n' || (c == '\r' && chars.get(i + 1) != Some(&'\n')) {
+                starts.push(i + 1);
+            }
+        }
+        starts
+    }
+
+    #[test]
+    fn crlf_split_between_leaves_is_one_break() {
+        let rope = from_chunks(&["ab\r", "\ncd\r", "\r\n", "e\n"]);
+        check(&rope);
+        assert_eq!(rope.len_lines(), 5);
+        assert_eq!(rope.line_to_char(1), 4);
+        assert_eq!(rope.char_to_line(2), 0);
+        assert_eq!(rope.char_to_line(3), 0); // Between the \r and the \n
+        assert_eq!(rope.char_to_line(4), 1);
+        assert_eq!(rope.line(0).to_string(), "ab\r\n");
+        assert_eq!(rope.line(1).to_string(), "cd\r");
+        assert_eq!(rope.line(2).to_string(), "\r\n");
+        assert_eq!(rope.line(4).to_string(), "");
+        assert_eq!(rope.line_to_byte(3), 9);
+        assert_eq!(rope.byte_to_line(10), 3);
+        assert_eq!(rope.byte_to_line(8), 2);
+    }
+
+    #[test]
+    fn lines_match_a_string() {
+        let pieces = ["x", "\r", "\n", "\r\n", "\u{e9}\u{e9}", "\u{1f980}"];
+        let mut rng = Rng(0x3c6e_f372_fe94_f82b);
+        for _ in 0..40 {
+            let text: String = (0..rng.below(3000))
+                .map(|_| pieces[rng.below(pieces.len())])
+                .collect();
+            let mut rope = Rope::from(text.as_str());
+            // Cut somewhere inside so seams fall in odd places
+            let at = rng.below(rope.len_chars() + 1);
+            rope.insert(at, "\n");
+            let mut model = text.clone();
+            model.insert(byte_of(&model, at), '\n');
+
+            let starts = line_starts(&model);
+            assert_eq!(rope.len_lines(), starts.len());
+            for (line, &start) in starts.iter().enumerate() {
+                assert_eq!(rope.line_to_char(line), start);
+            }
+            let mut line = 0;
+            for c in 0..=model.chars().count() {
+                while line + 1 < starts.len() && starts[line + 1] <= c {
+                    line += 1;
+                }
+                assert_eq!(rope.char_to_line(c), line, "char {}", c);
+            }
+        }
+    }
+}
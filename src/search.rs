@@ -0,0 +1,288 @@
+//NOTE: ---------------------- SEARCH -------------------------------------------
+//
+// Substring search over the bytes of a rope with Knuth-Morris-Pratt. KMP never
+// backs up in the text, so it streams across leaf boundaries without copying
+// anything. A UTF-8 pattern can only match at char boundaries, so byte matches
+// are turned into char indices by counting the lead bytes that go past.
+// Searching backwards runs the same automaton over the reversed pattern.
+
+use crate::iter::Bytes;
+use crate::rope::Rope;
+use crate::slice::RopeSlice;
+use std::iter::Rev;
+use std::ops::Range;
+
+struct Kmp {
+    pattern: Vec<u8>,
+    fail: Vec<usize>, // fail[i]: longest proper border of pattern[..=i]
+}
+
+impl Kmp {
+    fn new(pattern: Vec<u8>) -> Kmp {
+        let mut fail = vec![0; pattern.len()];
+        let mut k = 0;
+        for i in 1..pattern.len() {
+            while k > 0 && pattern[i] != pattern[k] {
+                k = fail[k - 1];
+            }
+            if pattern[i] == pattern[k] {
+                k += 1;
+            }
+            fail[i] = k;
+        }
+        Kmp { pattern, fail }
+    }
+
+    // The matched prefix length after reading `byte`.
+    fn step(&self, mut state: usize, byte: u8) -> usize {
+        while state > 0 && self.pattern[state] != byte {
+            state = self.fail[state - 1];
+        }
+        if self.pattern[state] == byte {
+            state += 1;
+        }
+        state
+    }
+}
+
+fn is_char_start(byte: u8) -> bool {
+    byte & 0xC0 != 0x80
+}
+
+// Non-overlapping matches, front to back, as char ranges. An empty pattern
+// matches at every char index, like `str::matches`.
+pub struct Matches<'a> {
+    bytes: Bytes<'a>,
+    kmp: Kmp,
+    state: usize,
+    pattern_chars: usize,
+    chars: usize, // Chars read so far, counted relative to the search start
+    offset: usize,
+    len_chars: usize,
+    done: bool,
+}
+
+impl<'a> Matches<'a> {
+    fn new(slice: RopeSlice<'a>, pattern: &str, offset: usize) -> Self {
+        Matches {
+            bytes: slice.bytes(),
+            kmp: Kmp::new(pattern.as_bytes().to_vec()),
+            state: 0,
+            pattern_chars: pattern.chars().count(),
+            chars: 0,
+            offset,
+            len_chars: slice.len_chars(),
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Matches<'_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        if self.done {
+            return None;
+        }
+        if self.kmp.pattern.is_empty() {
+            let at = self.offset + self.chars;
+            self.chars += 1;
+            self.done = self.chars > self.len_chars;
+            return Some(at..at);
+        }
+        for byte in self.bytes.by_ref() {
+            if is_char_start(byte) {
+                self.chars += 1;
+            }
+            self.state = self.kmp.step(self.state, byte);
+            if self.state == self.kmp.pattern.len() {
+                self.state = 0;
+                let end = self.offset + self.chars;
+                return Some(end - self.pattern_chars..end);
+            }
+        }
+        self.done = true;
+        None
+    }
+}
+
+// Finds the last match by reading backwards; used by `rfind`.
+fn rfind_in(bytes: Rev<Bytes<'_>>, pattern: &str) -> Option<usize> {
+    let kmp = Kmp::new(pattern.bytes().rev().collect());
+    let (mut state, mut chars_from_end) = (0, 0);
+    for byte in bytes {
+        if is_char_start(byte) {
+            chars_from_end += 1;
+        }
+        state = kmp.step(state, byte);
+        if state == kmp.pattern.len() {
+            return Some(chars_from_end);
+        }
+    }
+    None
+}
+
+impl<'a> RopeSlice<'a> {
+    // The char index of the first match starting at or after `from`. None
+    // when `from` is past the end.
+    pub fn find(&self, pattern: &str, from: usize) -> Option<usize> {
+        if from > self.len_chars() {
+            return None;
+        }
+        let rest = self.slice(from..);
+        Matches::new(rest, pattern, from).next().map(|m| m.start)
+    }
+
+    // The char index of the last match that ends at or before `end`. None
+    // when `end` is past the end.
+    pub fn rfind(&self, pattern: &str, end: usize) -> Option<usize> {
+        if end > self.len_chars() {
+            return None;
+        }
+        if pattern.is_empty() {
+            return Some(self.slice(..end).len_chars());
+        }
+        let chars_from_end = rfind_in(self.slice(..end).bytes().rev(), pattern)?;
+        Some(end - chars_from_end)
+    }
+
+    pub fn matches(&self, pattern: &str) -> Matches<'a> {
+        Matches::new(*self, pattern, 0)
+    }
+}
+
+impl Rope {
+    pub fn find(&self, pattern: &str, from: usize) -> Option<usize> {
+        self.slice(..).find(pattern, from)
+    }
+
+    pub fn rfind(&self, pattern: &str, end: usize) -> Option<usize> {
+        self.slice(..).rfind(pattern, end)
+    }
+
+    pub fn matches(&self, pattern: &str) -> Matches<'_> {
+        Matches::new(self.slice(..), pattern, 0)
+    }
+
+    // Replaces every non-overlapping match, scanning from the front, and
+    // returns how many there were.
+    pub fn replace_all(&mut self, pattern: &str, replacement: &str) -> usize {
+        let found: Vec<Range<usize>> = self.matches(pattern).collect();
+        for range in found.iter().rev() {
+            self.remove(range.clone());
+            self.insert(range.start, replacement);
+        }
+        found.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rope::tests::from_chunks;
+    use crate::tests::Rng;
+
+    fn chars_in(text: &str, byte: usize) -> usize {
+        text[..byte].chars().count()
+    }
+
+    // A rope of tiny random leaves, so matches straddle seams.
+    fn choppy(rng: &mut Rng, text: &str) -> Rope {
+        let chars: Vec<char> = text.chars().collect();
+        let mut chunks = Vec::new();
+        let mut at = 0;
+        while at < chars.len() {
+            let n = (1 + rng.below(4)).min(chars.len() - at);
+            chunks.push(chars[at..at + n].iter().collect::<String>());
+            at += n;
+        }
+        let chunks: Vec<&str> = chunks.iter().map(String::as_str).collect();
+        from_chunks(&chunks)
+    }
+
+    #[test]
+    fn search_matches_str_across_seams() {
+        let mut rng = Rng(0x3956_c25b_f348_b538);
+        let patterns = [
+            "a",
+            "ab",
+            "aab",
+            "abab",
+            "\u{e9}a",
+            "a\u{e9}\u{e9}",
+            "\u{1f980}",
+        ];
+        for _ in 0..60 {
+            let text: String = (0..rng.below(200))
+                .map(|_| ['a', 'b', '\u{e9}', '\u{1f980}'][rng.below(4)])
+                .collect();
+            let rope = choppy(&mut rng, &text);
+            let len = rope.len_chars();
+            for pattern in patterns {
+                let expected: Vec<Range<usize>> = text
+                    .match_indices(pattern)
+                    .map(|(at, m)| chars_in(&text, at)..chars_in(&text, at + m.len()))
+                    .collect();
+                assert_eq!(rope.matches(pattern).collect::<Vec<_>>(), expected);
+
+                let from = rng.below(len + 1);
+                let from_byte = rope.char_to_byte(from);
+                let found = text[from_byte..]
+                    .find(pattern)
+                    .map(|at| chars_in(&text, from_byte + at));
+                assert_eq!(
+                    rope.find(pattern, from),
+                    found,
+                    "{:?} from {}",
+                    pattern,
+                    from
+                );
+
+                let end = rng.below(len + 1);
+                let end_byte = rope.char_to_byte(end);
+                let found = text[..end_byte]
+                    .rfind(pattern)
+                    .map(|at| chars_in(&text, at));
+                assert_eq!(rope.rfind(pattern, end), found, "{:?} to {}", pattern, end);
+            }
+        }
+    }
+
+    #[test]
+    fn slices_search_relative_to_their_start() {
+        let rope = from_chunks(&["xxab", "abxx", "ab"]);
+        let slice = rope.slice(2..10); // "ababxxab"
+        assert_eq!(slice.find("ab", 1), Some(2));
+        assert_eq!(slice.rfind("ab", 8), Some(6));
+        assert_eq!(slice.rfind("ab", 7), Some(2));
+        assert_eq!(slice.rfind("ab", 1), None);
+        assert_eq!(slice.matches("ab").collect::<Vec<_>>(), [0..2, 2..4, 6..8]);
+        assert_eq!(rope.rfind("", 4), Some(4));
+        assert_eq!(rope.matches("").count(), rope.len_chars() + 1);
+    }
+
+    #[test]
+    fn positions_past_the_end_find_nothing() {
+        let rope = Rope::from("abab");
+        assert_eq!(rope.find("ab", 4), None);
+        assert_eq!(rope.find("", 4), Some(4));
+        assert_eq!(rope.find("ab", 5), None);
+        assert_eq!(rope.find("", 99), None);
+        assert_eq!(rope.rfind("ab", 5), None);
+        assert_eq!(rope.rfind("", 99), None);
+        let slice = rope.slice(1..3);
+        assert_eq!(slice.find("b", 3), None);
+        assert_eq!(slice.rfind("b", 3), None);
+    }
+
+    #[test]
+    fn replace_all_is_non_overlapping() {
+        let mut rope = from_chunks(&["aa", "aa", "a"]);
+        assert_eq!(rope.replace_all("aa", "b"), 2);
+        assert_eq!(rope.to_string(), "bba");
+        let mut rope = Rope::from("one two one");
+        assert_eq!(rope.replace_all("one", "1\u{e9}"), 2);
+        assert_eq!(rope.to_string(), "1\u{e9} two 1\u{e9}");
+        assert_eq!(rope.replace_all("zzz", "y"), 0);
+    }
+}
@@ -0,0 +1,425 @@
+//NOTE: ---------------------- SELECTIONS ---------------------------------------
+//
+// A set of cursors, one per range. The ranges are kept sorted by start and
+// never overlap: any that come to overlap after an edit or a move are merged.
+// They are indexed by start in a red-black tree, so the range under a
+// position is found in O(log n). Edits at every range are built into one
+// ChangeSet and applied in a single pass, and each range's new place is
+// worked out from the lengths edited before it.
+
+use crate::changeset::ChangeSet;
+use crate::column::line_text_len;
+use crate::rope::Rope;
+use crate::slice::RopeSlice;
+use crate::RedBlackTree;
+use std::fmt;
+use std::ops::Range;
+
+// One cursor. `head` is where the caret is and moves; `anchor` is the other
+// end and stays put while the selection is extended. Both are char indices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SelectionRange {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl SelectionRange {
+    pub fn new(anchor: usize, head: usize) -> Self {
+        SelectionRange { anchor, head }
+    }
+
+    pub fn point(pos: usize) -> Self {
+        SelectionRange::new(pos, pos)
+    }
+
+    pub fn start(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    pub fn end(&self) -> usize {
+        self.anchor.max(self.head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    fn is_backward(&self) -> bool {
+        self.head < self.anchor
+    }
+
+    // The same direction over `start..end`.
+    fn with_bounds(&self, start: usize, end: usize) -> Self {
+        if self.is_backward() {
+            SelectionRange::new(end, start)
+        } else {
+            SelectionRange::new(start, end)
+        }
+    }
+}
+
+// Covers both ranges, in the direction of whichever sorts first.
+fn merge(a: SelectionRange, b: SelectionRange) -> SelectionRange {
+    let first = if (a.start(), a.end()) <= (b.start(), b.end()) {
+        a
+    } else {
+        b
+    };
+    first.with_bounds(a.start().min(b.start()), a.end().max(b.end()))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Motion {
+    Chars(isize),
+    Words(isize), // To word starts going forward, and back to them going back
+    Lines(isize), // Keeps the column where the line is long enough
+}
+
+pub struct Selection {
+    ranges: RedBlackTree<usize, SelectionRange>, // Keyed by start
+}
+
+impl Selection {
+    pub fn new<I: IntoIterator<Item = SelectionRange>>(ranges: I) -> Self {
+        let mut sorted: Vec<SelectionRange> = ranges.into_iter().collect();
+        sorted.sort_by_key(|r| (r.start(), r.end()));
+        Selection::from_sorted(sorted)
+    }
+
+    pub fn point(pos: usize) -> Self {
+        Selection::new([SelectionRange::point(pos)])
+    }
+
+    // Merges ranges that overlap, or that start at the same place, then
+    // indexes what is left. A merged range keeps the first one's direction.
+    fn from_sorted(sorted: Vec<SelectionRange>) -> Self {
+        let mut merged: Vec<SelectionRange> = Vec::with_capacity(sorted.len());
+        for range in sorted {
+            match merged.last_mut() {
+                Some(last) if range.start() < last.end() || range.start() == last.start() => {
+                    *last = merge(*last, range);
+                }
+                _ => merged.push(range),
+            }
+        }
+        let mut tree = RedBlackTree::new();
+        for range in merged {
+            tree.insert(range.start(), range);
+        }
+        Selection { ranges: tree }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.tree_size() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    // In document order.
+    pub fn ranges(&self) -> Vec<SelectionRange> {
+        let mut entries = Vec::new();
+        self.ranges.in_order(&self.ranges.root, &mut entries);
+        entries.into_iter().map(|(_, range)| *range).collect()
+    }
+
+    // Merges `range` with the ranges it overlaps, in O(log n) plus O(log n)
+    // per range it swallows: only the range starting at or before it, and
+    // those starting inside it, can overlap it.
+    pub fn add(&mut self, range: SelectionRange) {
+        let mut merged = range;
+        if let Some((&start, &before)) = self.ranges.floor_entry(&range.start()) {
+            if range.start() < before.end() || range.start() == start {
+                self.ranges.delete(&start);
+                merged = merge(before, merged);
+            }
+        }
+        while let Some((&start, &after)) = self.ranges.ceiling_entry(&merged.start()) {
+            if start >= merged.end() {
+                break;
+            }
+            self.ranges.delete(&start);
+            merged = merge(merged, after);
+        }
+        self.ranges.insert(merged.start(), merged);
+    }
+
+    // The range that covers `char_idx`, ends included.
+    pub fn range_at(&self, char_idx: usize) -> Option<SelectionRange> {
+        let (_, range) = self.ranges.floor_entry(&char_idx)?;
+        (char_idx <= range.end()).then_some(*range)
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Editing
+    //   ***************************************************************************
+    // Types `text` at every cursor: the selected text is replaced and each
+    // cursor ends up just past what it typed.
+    pub fn insert_at_all(&mut self, rope: &mut Rope, text: &str) -> ChangeSet {
+        let text = rope.converted(text).into_owned();
+        let edits = self
+            .ranges()
+            .into_iter()
+            .map(|r| (r, r.start()..r.end(), text.clone()))
+            .collect();
+        self.batch(rope, edits, |_, new| SelectionRange::point(new.end))
+    }
+
+    // Deletes the selected text; a cursor with nothing selected deletes the
+    // char before it, or the whole line break when that is a CRLF.
+    pub fn delete_at_all(&mut self, rope: &mut Rope) -> ChangeSet {
+        let edits = self
+            .ranges()
+            .into_iter()
+            .map(|r| {
+                let mut start = r.start();
+                if r.is_empty() && start > 0 {
+                    start -= 1;
+                    if rope.inside_crlf(start) {
+                        start -= 1;
+                    }
+                }
+                (r, start..r.end(), String::new())
+            })
+            .collect();
+        self.batch(rope, edits, |_, new| SelectionRange::point(new.start))
+    }
+
+    // Replaces each selection's text with what `f` returns for it. The new
+    // text stays selected, in the same direction as before.
+    pub fn replace_each<F>(&mut self, rope: &mut Rope, mut f: F) -> ChangeSet
+    where
+        F: FnMut(RopeSlice<'_>) -> String,
+    {
+        let edits = self
+            .ranges()
+            .into_iter()
+            .map(|r| {
+                let text = f(rope.slice(r.start()..r.end()));
+                (r, r.start()..r.end(), rope.converted(&text).into_owned())
+            })
+            .collect();
+        self.batch(rope, edits, |r, new| r.with_bounds(new.start, new.end))
+    }
+
+    // Applies every edit as one ChangeSet and returns it. An edit is the
+    // range it belongs to, the chars to replace and their replacement;
+    // `place` gets where the replacement landed and returns the new range.
+    fn batch<P>(
+        &mut self,
+        rope: &mut Rope,
+        edits: Vec<(SelectionRange, Range<usize>, String)>,
+        place: P,
+    ) -> ChangeSet
+    where
+        P: Fn(&SelectionRange, Range<usize>) -> SelectionRange,
+    {
+        let mut changes = ChangeSet::new();
+        let mut placed = Vec::with_capacity(edits.len());
+        let mut pos = 0; // Chars of the old text covered so far
+        let mut shift = 0isize; // New position minus old, past `pos`
+        for (range, replaced, text) in &edits {
+            // A backspace may reach into the range before it
+            let start = replaced.start.max(pos);
+            let end = replaced.end.max(start);
+            changes.retain(start - pos).delete(end - start).insert(text);
+            let new_start = (start as isize + shift) as usize;
+            let inserted = text.chars().count();
+            placed.push(place(range, new_start..new_start + inserted));
+            shift += inserted as isize - (end - start) as isize;
+            pos = end;
+        }
+        changes.retain(rope.len_chars() - pos);
+        changes
+            .apply(rope)
+            .expect("selection ranges lie inside the rope");
+        *self = Selection::from_sorted(placed);
+        changes
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Moving
+    //   ***************************************************************************
+    // Moves every head. With `extend` the anchors stay where they are;
+    // otherwise each range collapses to its new head.
+    pub fn move_by(&mut self, rope: &Rope, motion: Motion, extend: bool) {
+        let moved = self.ranges().into_iter().map(|r| {
+            let head = match motion {
+                Motion::Chars(n) => move_chars(rope, r.head, n),
+                Motion::Words(n) => move_words(rope, r.head, n),
+                Motion::Lines(n) => move_lines(rope, r.head, n),
+            };
+            let anchor = if extend { r.anchor } else { head };
+            SelectionRange::new(anchor, head)
+        });
+        *self = Selection::new(moved.collect::<Vec<_>>());
+    }
+}
+
+// Steps over a CRLF as one char.
+fn move_chars(rope: &Rope, pos: usize, n: isize) -> usize {
+    let mut pos = pos.min(rope.len_chars());
+    for _ in 0..n.unsigned_abs() {
+        if n > 0 && pos < rope.len_chars() {
+            pos += 1 + rope.inside_crlf(pos + 1) as usize;
+        } else if n < 0 && pos > 0 {
+            pos -= 1 + rope.inside_crlf(pos - 1) as usize;
+        }
+    }
+    pos
+}
+
+fn move_words(rope: &Rope, pos: usize, n: isize) -> usize {
+    let mut pos = pos.min(rope.len_chars());
+    for _ in 0..n.unsigned_abs() {
+        pos = if n > 0 {
+            rope.next_word_start(pos)
+        } else {
+            rope.prev_word_start(pos)
+        };
+    }
+    pos
+}
+
+fn move_lines(rope: &Rope, pos: usize, n: isize) -> usize {
+    let pos = pos.min(rope.len_chars());
+    let line = rope.char_to_line(pos);
+    let column = pos - rope.line_to_char(line);
+    let last = rope.len_lines().saturating_sub(1) as isize;
+    let target = (line as isize + n).clamp(0, last) as usize;
+    rope.line_to_char(target) + column.min(line_text_len(rope.line(target)))
+}
+
+impl Clone for Selection {
+    fn clone(&self) -> Self {
+        Selection::from_sorted(self.ranges())
+    }
+}
+
+impl fmt::Debug for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.ranges()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Rng;
+    use crate::Node;
+
+    fn depth(node: &Option<Box<Node<usize, SelectionRange>>>) -> usize {
+        node.as_ref()
+            .map_or(0, |n| 1 + depth(&n.left).max(depth(&n.right)))
+    }
+
+    #[test]
+    fn overlapping_ranges_merge() {
+        let sel = Selection::new([
+            SelectionRange::new(8, 4),
+            SelectionRange::new(0, 2),
+            SelectionRange::new(6, 10),
+            SelectionRange::point(0),
+        ]);
+        assert_eq!(
+            sel.ranges(),
+            vec![SelectionRange::new(0, 2), SelectionRange::new(10, 4)]
+        );
+        assert_eq!(sel.range_at(7), Some(SelectionRange::new(10, 4)));
+        assert_eq!(sel.range_at(3), None);
+    }
+
+    #[test]
+    fn add_merges_like_new() {
+        let mut rng = Rng(0x59f1_11f1_923f_82a4);
+        let mut sel = Selection::point(0);
+        let mut all = vec![SelectionRange::point(0)];
+        for _ in 0..500 {
+            let anchor = rng.below(200);
+            let head = anchor + rng.below(8) - rng.below(8).min(anchor);
+            let range = SelectionRange::new(anchor, head);
+            sel.add(range);
+            all.push(range);
+            let bounds = |sel: &Selection| -> Vec<(usize, usize)> {
+                sel.ranges().iter().map(|r| (r.start(), r.end())).collect()
+            };
+            assert_eq!(bounds(&sel), bounds(&Selection::new(all.clone())));
+        }
+        assert_eq!(sel.len(), sel.ranges().len());
+
+        // The range that starts first keeps its direction
+        let mut sel = Selection::new([SelectionRange::new(6, 2), SelectionRange::point(9)]);
+        sel.add(SelectionRange::new(4, 10));
+        assert_eq!(sel.ranges(), [SelectionRange::new(10, 2)]);
+    }
+
+    #[test]
+    fn many_cursors_stay_indexed() {
+        let n = 20_000;
+        let mut rope = Rope::from("ab\n".repeat(n).as_str());
+        let mut sel = Selection::new((0..n).rev().map(|i| SelectionRange::point(3 * i)));
+        assert_eq!(sel.len(), n);
+        assert!(depth(&sel.ranges.root) <= 2 * 15); // 2 * log2(n + 1)
+        assert_eq!(sel.range_at(3 * 777 + 1), None);
+        assert_eq!(sel.range_at(3 * 777), Some(SelectionRange::point(3 * 777)));
+
+        sel.insert_at_all(&mut rope, "-");
+        assert_eq!(rope.len_chars(), 4 * n);
+        assert_eq!(rope.slice(..8).to_string(), "-ab\n-ab\n");
+        assert_eq!(sel.ranges()[n - 1], SelectionRange::point(4 * (n - 1) + 1));
+    }
+
+    #[test]
+    fn edits_at_every_cursor() {
+        let mut rope = Rope::from("one\r\ntwo\r\nsix");
+        let mut sel = Selection::new([
+            SelectionRange::point(5),
+            SelectionRange::new(6, 7),
+            SelectionRange::point(13),
+        ]);
+        sel.delete_at_all(&mut rope);
+        assert_eq!(rope.to_string(), "oneto\r\nsi");
+        assert_eq!(
+            sel.ranges(),
+            vec![
+                SelectionRange::point(3),
+                SelectionRange::point(4),
+                SelectionRange::point(9)
+            ]
+        );
+
+        sel.insert_at_all(&mut rope, "_");
+        assert_eq!(rope.to_string(), "one_t_o\r\nsi_");
+
+        let mut sel = Selection::new([SelectionRange::new(3, 0), SelectionRange::new(4, 5)]);
+        let changes = sel.replace_each(&mut rope, |s| s.to_string().to_uppercase());
+        assert_eq!(rope.to_string(), "ONE_T_o\r\nsi_");
+        assert_eq!(changes.len_before(), changes.len_after());
+        assert_eq!(
+            sel.ranges(),
+            vec![SelectionRange::new(3, 0), SelectionRange::new(4, 5)]
+        );
+    }
+
+    #[test]
+    fn motions_merge_cursors_that_meet() {
+        let rope = Rope::from("ab\r\ncd\nlonger line");
+        let mut sel = Selection::new([SelectionRange::point(1), SelectionRange::point(2)]);
+        sel.move_by(&rope, Motion::Chars(1), false);
+        assert_eq!(
+            sel.ranges(),
+            vec![SelectionRange::point(2), SelectionRange::point(4)]
+        );
+        sel.move_by(&rope, Motion::Chars(-2), true);
+        assert_eq!(sel.ranges(), vec![SelectionRange::new(4, 0)]);
+
+        let mut sel = Selection::point(13);
+        sel.move_by(&rope, Motion::Lines(-1), false);
+        assert_eq!(sel.ranges(), vec![SelectionRange::point(6)]);
+        sel.move_by(&rope, Motion::Lines(-1), false);
+        assert_eq!(sel.ranges(), vec![SelectionRange::point(2)]);
+        sel.move_by(&rope, Motion::Words(2), false);
+        assert_eq!(sel.ranges(), vec![SelectionRange::point(7)]);
+    }
+}
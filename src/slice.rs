@@ -0,0 +1,431 @@
+//NOTE: ---------------------- ROPE SLICES ---------------------------------------
+//
+// A RopeSlice is a char range of a rope, borrowed rather than copied. Indices
+// passed to and returned from a slice are relative to its start, and line
+// breaks are counted as if the slice were its own text.
+
+use crate::iter::{check_index, Bytes, Chars, Chunks, Lines};
+use crate::rope::{resolve_range, Rope};
+use crate::RangeError;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Range, RangeBounds};
+
+#[derive(Copy, Clone)]
+pub struct RopeSlice<'a> {
+    rope: &'a Rope,
+    start: usize, // Chars
+    end: usize,
+    start_byte: usize,
+    end_byte: usize,
+}
+
+impl<'a> RopeSlice<'a> {
+    pub(crate) fn new(rope: &'a Rope, start: usize, end: usize) -> RopeSlice<'a> {
+        RopeSlice {
+            rope,
+            start,
+            end,
+            start_byte: rope.char_to_byte(start),
+            end_byte: rope.char_to_byte(end),
+        }
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.end_byte - self.start_byte
+    }
+
+    pub fn len_utf16(&self) -> usize {
+        self.rope.char_to_utf16(self.end) - self.rope.char_to_utf16(self.start)
+    }
+
+    pub fn len_lines(&self) -> usize {
+        self.breaks_before(self.len_chars()) + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> RopeSlice<'a> {
+        self.try_slice(range).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_slice<R: RangeBounds<usize>>(&self, range: R) -> Result<RopeSlice<'a>, RangeError> {
+        let Range { start, end } = resolve_range(range, self.len_chars())?;
+        Ok(RopeSlice::new(
+            self.rope,
+            self.start + start,
+            self.start + end,
+        ))
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Index conversion
+    //   ***************************************************************************
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        check_index(char_idx, self.len_chars());
+        self.rope.char_to_byte(self.start + char_idx) - self.start_byte
+    }
+
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        check_index(byte_idx, self.len_bytes());
+        self.rope.byte_to_char(self.start_byte + byte_idx) - self.start
+    }
+
+    pub fn char_to_utf16(&self, char_idx: usize) -> usize {
+        check_index(char_idx, self.len_chars());
+        self.rope.char_to_utf16(self.start + char_idx) - self.rope.char_to_utf16(self.start)
+    }
+
+    pub fn utf16_to_char(&self, utf16_idx: usize) -> usize {
+        check_index(utf16_idx, self.len_utf16());
+        let base = self.rope.char_to_utf16(self.start);
+        self.rope.utf16_to_char(base + utf16_idx) - self.start
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Lines
+    //   ***************************************************************************
+    // Line breaks completed in the first `char_idx` chars of the slice. A slice
+    // that starts between `\r` and `\n` begins with a break of its own.
+    fn breaks_before(&self, char_idx: usize) -> usize {
+        if char_idx == 0 {
+            return 0;
+        }
+        let base = self.rope.prefix_info(self.start).line_breaks;
+        let own_lf = self.rope.inside_crlf(self.start) as usize;
+        self.rope.prefix_info(self.start + char_idx).line_breaks - base + own_lf
+    }
+
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        check_index(char_idx, self.len_chars());
+        let breaks = self.breaks_before(char_idx);
+        if char_idx > 0
+            && char_idx < self.len_chars()
+            && self.rope.inside_crlf(self.start + char_idx)
+        {
+            breaks - 1
+        } else {
+            breaks
+        }
+    }
+
+    pub fn line_to_char(&self, line_idx: usize) -> usize {
+        check_index(line_idx, self.len_lines());
+        if line_idx == 0 {
+            return 0;
+        }
+        if line_idx == self.len_lines() {
+            return self.len_chars();
+        }
+        let base = self.rope.prefix_info(self.start).line_breaks;
+        let own_lf = self.rope.inside_crlf(self.start) as usize;
+        // A `\r` at the very end is a break here even if the rope goes on
+        // with `\n`, hence the clamp.
+        let start = self.rope.line_to_char(base + line_idx - own_lf);
+        (start - self.start).min(self.len_chars())
+    }
+
+    pub fn line_to_byte(&self, line_idx: usize) -> usize {
+        self.char_to_byte(self.line_to_char(line_idx))
+    }
+
+    pub fn byte_to_line(&self, byte_idx: usize) -> usize {
+        self.char_to_line(self.byte_to_char(byte_idx))
+    }
+
+    // Line `line_idx` of the slice, including its line break.
+    pub fn line(&self, line_idx: usize) -> RopeSlice<'a> {
+        if line_idx >= self.len_lines() {
+            panic!(
+                "{}",
+                RangeError::OutOfBounds {
+                    index: line_idx,
+                    len: self.len_lines()
+                }
+            );
+        }
+        self.slice(self.line_to_char(line_idx)..self.line_to_char(line_idx + 1))
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Iterators
+    //   ***************************************************************************
+    pub fn chunks(&self) -> Chunks<'a> {
+        Chunks::new(self.rope, self.start_byte, self.end_byte)
+    }
+
+    pub fn bytes(&self) -> Bytes<'a> {
+        self.bytes_at(0)
+    }
+
+    pub fn bytes_at(&self, byte_idx: usize) -> Bytes<'a> {
+        check_index(byte_idx, self.len_bytes());
+        Bytes::new(self.rope, self.start_byte + byte_idx, self.end_byte)
+    }
+
+    pub fn chars(&self) -> Chars<'a> {
+        self.chars_at(0)
+    }
+
+    pub fn chars_at(&self, char_idx: usize) -> Chars<'a> {
+        check_index(char_idx, self.len_chars());
+        Chars::new(self.rope, self.start + char_idx, self.end)
+    }
+
+    pub fn lines(&self) -> Lines<'a> {
+        self.lines_at(0)
+    }
+
+    pub fn lines_at(&self, line_idx: usize) -> Lines<'a> {
+        Lines::new(*self, line_idx)
+    }
+}
+
+impl fmt::Display for RopeSlice<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for RopeSlice<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RopeSlice").field(&self.to_string()).finish()
+    }
+}
+
+impl<'a> From<RopeSlice<'a>> for Rope {
+    fn from(slice: RopeSlice<'a>) -> Self {
+        let mut rope = Rope::new();
+        for chunk in slice.chunks() {
+            rope.concat(Rope::from(chunk));
+        }
+        rope
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Comparison
+//   ***************************************************************************
+fn eq_str(slice: &RopeSlice<'_>, text: &str) -> bool {
+    if slice.len_bytes() != text.len() {
+        return false;
+    }
+    let mut rest = text.as_bytes();
+    for chunk in slice.chunks() {
+        if !rest.starts_with(chunk.as_bytes()) {
+            return false;
+        }
+        rest = &rest[chunk.len()..];
+    }
+    true
+}
+
+fn eq_slices(a: &RopeSlice<'_>, b: &RopeSlice<'_>) -> bool {
+    a.len_bytes() == b.len_bytes() && a.bytes().eq(b.bytes())
+}
+
+impl<'b> PartialEq<RopeSlice<'b>> for RopeSlice<'_> {
+    fn eq(&self, other: &RopeSlice<'b>) -> bool {
+        eq_slices(self, other)
+    }
+}
+
+impl Eq for RopeSlice<'_> {}
+
+impl PartialEq for Rope {
+    fn eq(&self, other: &Rope) -> bool {
+        eq_slices(&self.slice(..), &other.slice(..))
+    }
+}
+
+impl Eq for Rope {}
+
+// Hashers need not treat several writes like one write of the same bytes, so
+// the text is fed in fixed-size blocks that don't depend on the chunking.
+fn hash_slice<H: Hasher>(slice: &RopeSlice<'_>, state: &mut H) {
+    const BLOCK: usize = 256;
+    let mut block = [0u8; BLOCK];
+    let mut filled = 0;
+    for mut bytes in slice.chunks().map(str::as_bytes) {
+        while !bytes.is_empty() {
+            let n = bytes.len().min(BLOCK - filled);
+            block[filled..filled + n].copy_from_slice(&bytes[..n]);
+            filled += n;
+            bytes = &bytes[n..];
+            if filled == BLOCK {
+                state.write(&block);
+                filled = 0;
+            }
+        }
+    }
+    state.write(&block[..filled]);
+    state.write_u8(0xff);
+}
+
+impl Hash for RopeSlice<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_slice(self, state);
+    }
+}
+
+impl Hash for Rope {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_slice(&self.slice(..), state);
+    }
+}
+
+impl PartialEq<RopeSlice<'_>> for Rope {
+    fn eq(&self, other: &RopeSlice<'_>) -> bool {
+        eq_slices(&self.slice(..), other)
+    }
+}
+
+impl PartialEq<Rope> for RopeSlice<'_> {
+    fn eq(&self, other: &Rope) -> bool {
+        eq_slices(self, &other.slice(..))
+    }
+}
+
+macro_rules! impl_eq_str {
+    ($($text:ty),*) => {
+        $(
+            impl PartialEq<$text> for RopeSlice<'_> {
+                fn eq(&self, other: &$text) -> bool {
+                    eq_str(self, other)
+                }
+            }
+
+            impl PartialEq<RopeSlice<'_>> for $text {
+                fn eq(&self, other: &RopeSlice<'_>) -> bool {
+                    eq_str(other, self)
+                }
+            }
+
+            impl PartialEq<$text> for Rope {
+                fn eq(&self, other: &$text) -> bool {
+                    eq_str(&self.slice(..), other)
+                }
+            }
+
+            impl PartialEq<Rope> for $text {
+                fn eq(&self, other: &Rope) -> bool {
+                    eq_str(&other.slice(..), self)
+                }
+            }
+        )*
+    };
+}
+
+impl_eq_str!(str, &str, String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rope::tests::from_chunks;
+    use crate::tests::Rng;
+    use std::collections::hash_map::DefaultHasher;
+
+    // Everything a slice reports should match a rope of just its text.
+    fn check_against_own_text(slice: RopeSlice<'_>) {
+        let own = Rope::from(slice.to_string().as_str());
+        assert_eq!(slice.len_chars(), own.len_chars());
+        assert_eq!(slice.len_bytes(), own.len_bytes());
+        assert_eq!(slice.len_utf16(), own.len_utf16());
+        assert_eq!(slice.len_lines(), own.len_lines());
+        for c in 0..=own.len_chars() {
+            assert_eq!(slice.char_to_line(c), own.char_to_line(c), "char {}", c);
+            assert_eq!(slice.char_to_byte(c), own.char_to_byte(c));
+            assert_eq!(slice.char_to_utf16(c), own.char_to_utf16(c));
+        }
+        for line in 0..=own.len_lines() {
+            assert_eq!(
+                slice.line_to_char(line),
+                own.line_to_char(line),
+                "line {}",
+                line
+            );
+        }
+        assert!(slice.lines().eq(own.lines()));
+        assert!(slice.chars().rev().eq(own.chars().rev()));
+        assert_eq!(slice, own);
+    }
+
+    #[test]
+    fn slices_cutting_a_crlf() {
+        let rope = from_chunks(&["a\r", "\nb\r\n", "c"]);
+        // Starts between the \r and \n: the \n is a line of its own
+        let tail = rope.slice(2..);
+        assert_eq!(tail.len_lines(), 3);
+        assert_eq!(tail.line(0), "\n");
+        check_against_own_text(tail);
+        // Ends between them: the \r ends the line
+        let head = rope.slice(..2);
+        assert_eq!(head.len_lines(), 2);
+        assert_eq!(head.line(0), "a\r");
+        check_against_own_text(head);
+        check_against_own_text(rope.slice(2..5));
+    }
+
+    #[test]
+    fn random_slices_act_like_their_text() {
+        let mut rng = Rng(0x510e_527f_ade6_82d1);
+        let pieces = ["a", "\r", "\n", "\r\n", "\u{e9}", "\u{1f980}"];
+        let text: String = (0..1500).map(|_| pieces[rng.below(pieces.len())]).collect();
+        let rope = Rope::from(text.as_str());
+        for _ in 0..30 {
+            let start = rng.below(rope.len_chars() + 1);
+            let end = start + rng.below((rope.len_chars() - start).min(200) + 1);
+            let slice = rope.slice(start..end);
+            check_against_own_text(slice);
+            let inner = rng.below(slice.len_chars() + 1);
+            check_against_own_text(slice.slice(inner..));
+        }
+    }
+
+    #[test]
+    fn equality_and_hash_ignore_chunking() {
+        let hash = |value: &dyn Fn(&mut DefaultHasher)| {
+            let mut hasher = DefaultHasher::new();
+            value(&mut hasher);
+            hasher.finish()
+        };
+        let text = "x".repeat(300) + "\u{e9}" + &"y".repeat(300);
+        let one = Rope::from(text.as_str());
+        let many = from_chunks(&[
+            "x".repeat(100).as_str(),
+            &"x".repeat(200),
+            "\u{e9}yy",
+            &"y".repeat(298),
+        ]);
+        assert_eq!(one, many);
+        assert_eq!(one.slice(10..400), many.slice(10..400));
+        assert_eq!(one, text.as_str());
+        assert_ne!(one.slice(0..3), "xxy");
+        assert_eq!(hash(&|h| one.hash(h)), hash(&|h| many.hash(h)));
+        assert_eq!(
+            hash(&|h| one.slice(5..350).hash(h)),
+            hash(&|h| many.slice(5..350).hash(h))
+        );
+    }
+
+    #[test]
+    fn try_slice_reports_bad_ranges() {
+        let rope = Rope::from("abc");
+        let slice = rope.slice(1..);
+        assert_eq!(
+            slice.try_slice(1..3).unwrap_err(),
+            RangeError::OutOfBounds { index: 3, len: 2 }
+        );
+        assert_eq!(slice.slice(1..).to_string(), "c");
+        assert_eq!(Rope::from(slice), "bc");
+    }
+}
@@ -0,0 +1,336 @@
+//NOTE: ---------------------- STREAMING I/O ------------------------------------
+//
+// Ropes are built from readers a buffer at a time and written out a chunk at
+// a time, so a large file is never held as one String. RopeBuilder cuts the
+// incoming text into full leaves and balances them once at the end.
+
+use crate::iter::Chunks;
+use crate::rope::{build_from_chunks, Rope, MAX_CHUNK_BYTES};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+const READ_BUFFER_BYTES: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    // Byte offset, from the start of the stream, of the first bad sequence.
+    InvalidUtf8 { offset: usize },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "read failed: {}", e),
+            ReadError::InvalidUtf8 { offset } => write!(f, "invalid UTF-8 at byte {}", offset),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReadError::Io(e) => Some(e),
+            ReadError::InvalidUtf8 { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Building
+//   ***************************************************************************
+#[derive(Default)]
+pub struct RopeBuilder {
+    chunks: Vec<String>, // Full leaves
+    pending: String,     // The leaf being filled
+}
+
+impl RopeBuilder {
+    pub fn new() -> Self {
+        RopeBuilder::default()
+    }
+
+    pub fn append(&mut self, mut text: &str) -> &mut Self {
+        while !text.is_empty() {
+            let room = MAX_CHUNK_BYTES - self.pending.len();
+            if text.len() <= room {
+                self.pending.push_str(text);
+                break;
+            }
+            let mut at = room;
+            while !text.is_char_boundary(at) {
+                at -= 1;
+            }
+            self.pending.push_str(&text[..at]);
+            text = &text[at..];
+            self.flush();
+        }
+        self
+    }
+
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            let chunk =
+                std::mem::replace(&mut self.pending, String::with_capacity(MAX_CHUNK_BYTES));
+            self.chunks.push(chunk);
+        }
+    }
+
+    pub fn finish(mut self) -> Rope {
+        self.flush();
+        let mut rope = Rope::new();
+        rope.root = build_from_chunks(self.chunks);
+        rope
+    }
+}
+
+impl fmt::Write for RopeBuilder {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.append(text);
+        Ok(())
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Reading and writing
+//   ***************************************************************************
+impl Rope {
+    // A char split across two reads is carried over to the next one.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Rope, ReadError> {
+        let mut builder = RopeBuilder::new();
+        let mut buffer = vec![0; READ_BUFFER_BYTES];
+        let mut carried = 0; // Bytes of an unfinished char at the buffer start
+        let mut offset = 0; // Stream offset of buffer[0]
+        loop {
+            let read = match reader.read(&mut buffer[carried..]) {
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let filled = carried + read;
+            let (valid, rest) = match std::str::from_utf8(&buffer[..filled]) {
+                Ok(text) => (text, 0),
+                Err(e) => {
+                    // Only a truncated char at the very end may wait for more
+                    // input; anything else is bad data.
+                    if e.error_len().is_some() || read == 0 {
+                        return Err(ReadError::InvalidUtf8 {
+                            offset: offset + e.valid_up_to(),
+                        });
+                    }
+                    let text = std::str::from_utf8(&buffer[..e.valid_up_to()]).unwrap();
+                    (text, filled - e.valid_up_to())
+                }
+            };
+            builder.append(valid);
+            if read == 0 {
+                return Ok(builder.finish());
+            }
+            let consumed = filled - rest;
+            buffer.copy_within(consumed..filled, 0);
+            carried = rest;
+            offset += consumed;
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for chunk in self.chunks() {
+            writer.write_all(chunk.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn reader(&self) -> RopeReader<'_> {
+        RopeReader::new(self)
+    }
+}
+
+// Reads the rope's bytes through `io::Read`, with seeking by byte offset.
+pub struct RopeReader<'a> {
+    rope: &'a Rope,
+    pos: usize, // May be past the end after a seek
+    chunks: Chunks<'a>,
+    current: &'a [u8], // Unread part of the chunk holding `pos`
+}
+
+impl<'a> RopeReader<'a> {
+    pub fn new(rope: &'a Rope) -> Self {
+        let mut reader = RopeReader {
+            rope,
+            pos: 0,
+            chunks: Chunks::new(rope, 0, 0),
+            current: &[],
+        };
+        reader.reposition(0);
+        reader
+    }
+
+    fn reposition(&mut self, pos: usize) {
+        let len = self.rope.len_bytes();
+        let leaf_start = Chunks::leaf_start(self.rope, pos.min(len));
+        self.chunks = Chunks::new(self.rope, leaf_start, len);
+        self.current = &[];
+        if pos < len {
+            let first = self.chunks.next().unwrap_or("");
+            self.current = &first.as_bytes()[pos - leaf_start..];
+        }
+        self.pos = pos;
+    }
+}
+
+impl Read for RopeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut written = 0;
+        while written < buf.len() {
+            if self.current.is_empty() {
+                match self.chunks.next() {
+                    Some(chunk) => self.current = chunk.as_bytes(),
+                    None => break,
+                }
+            }
+            let n = self.current.len().min(buf.len() - written);
+            buf[written..written + n].copy_from_slice(&self.current[..n]);
+            self.current = &self.current[n..];
+            written += n;
+        }
+        self.pos += written;
+        Ok(written)
+    }
+}
+
+impl Seek for RopeReader<'_> {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        let (base, delta) = match from {
+            SeekFrom::Start(n) => (0, n as i64),
+            SeekFrom::End(n) => (self.rope.len_bytes() as i64, n),
+            SeekFrom::Current(n) => (self.pos as i64, n),
+        };
+        let pos = base
+            .checked_add(delta)
+            .filter(|&p| p >= 0)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start"))?;
+        self.reposition(pos as usize);
+        Ok(pos as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rope::tests::check;
+
+    // Hands out at most `step` bytes per read, and fails every other read
+    // with `Interrupted`.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+        interrupt: bool,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let n = self.step.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    fn trickle(data: &[u8], step: usize) -> Trickle<'_> {
+        Trickle {
+            data,
+            step,
+            interrupt: false,
+        }
+    }
+
+    fn invalid_at(result: Result<Rope, ReadError>) -> usize {
+        match result {
+            Err(ReadError::InvalidUtf8 { offset }) => offset,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("read invalid UTF-8"),
+        }
+    }
+
+    #[test]
+    fn one_byte_reads_rebuild_split_chars() {
+        let text = "h\u{e9}llo \u{1f980}\u{65e5}\r\n".repeat(400);
+        for step in [1, 2, 3, 7, 4096] {
+            let rope = Rope::from_reader(trickle(text.as_bytes(), step)).unwrap();
+            check(&rope);
+            assert_eq!(rope.to_string(), text);
+            assert!(rope.chunks().all(|c| c.len() <= MAX_CHUNK_BYTES));
+        }
+        assert!(Rope::from_reader(trickle(b"", 1)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_utf8_reports_the_stream_offset() {
+        let mut data = "\u{e9}".repeat(5000).into_bytes();
+        data.insert(7000, 0xFF); // Between two chars
+        for step in [1, 5, 8192] {
+            assert_eq!(invalid_at(Rope::from_reader(trickle(&data, step))), 7000);
+        }
+        // A char cut off by the end of the stream
+        assert_eq!(invalid_at(Rope::from_reader(trickle(b"ab\xE2\x82", 1))), 2);
+        // A continuation byte with no lead
+        assert_eq!(invalid_at(Rope::from_reader(&b"ab\x82c"[..])), 2);
+    }
+
+    #[test]
+    fn reader_writer_and_seek() {
+        let text = "0123456789\u{e9}".repeat(300);
+        let rope = Rope::from(text.as_str());
+        let mut written = Vec::new();
+        rope.write_to(&mut written).unwrap();
+        assert_eq!(written, text.as_bytes());
+
+        let mut reader = rope.reader();
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, text.as_bytes());
+
+        let mut buf = [0; 4];
+        assert_eq!(reader.seek(SeekFrom::Start(1200)).unwrap(), 1200);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, text.as_bytes()[1200..1204]);
+        assert_eq!(reader.seek(SeekFrom::Current(-2)).unwrap(), 1202);
+        assert_eq!(
+            reader.seek(SeekFrom::End(-3)).unwrap() as usize,
+            text.len() - 3
+        );
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(
+            reader.seek(SeekFrom::End(10)).unwrap() as usize,
+            text.len() + 10
+        );
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert!(reader.seek(SeekFrom::Current(-1_000_000)).is_err());
+    }
+
+    #[test]
+    fn builder_fills_whole_leaves() {
+        let mut builder = RopeBuilder::new();
+        for _ in 0..1000 {
+            builder.append("ab\u{1f980}");
+        }
+        let rope = builder.finish();
+        check(&rope);
+        assert_eq!(rope.to_string(), "ab\u{1f980}".repeat(1000));
+        let leaves: Vec<usize> = rope.chunks().map(str::len).collect();
+        assert!(leaves[..leaves.len() - 1]
+            .iter()
+            .all(|&len| len > MAX_CHUNK_BYTES - 4));
+    }
+}
@@ -0,0 +1,321 @@
+//NOTE: ---------------------- SVG RENDERING ------------------------------------
+//
+// Draws a tree as a standalone SVG, with no dependencies. Nodes are placed
+// Reingold-Tilford style: each subtree is laid out on its own, then the two
+// children of a node are pushed apart until no level of one overlaps the
+// same level of the other, and the parent is centered above them.
+
+use crate::{Node, RedBlackTree};
+use std::fmt::{Display, Write};
+
+pub struct SvgOptions<K> {
+    pub node_radius: f64,
+    pub level_gap: f64,
+    pub sibling_gap: f64,
+    pub margin: f64,
+    pub show_size: bool,
+    pub highlight: Vec<K>, // Keys drawn with an outline, e.g. a search path
+}
+
+impl<K> Default for SvgOptions<K> {
+    fn default() -> Self {
+        SvgOptions {
+            node_radius: 18.0,
+            level_gap: 60.0,
+            sibling_gap: 12.0,
+            margin: 20.0,
+            show_size: true,
+            highlight: Vec::new(),
+        }
+    }
+}
+
+// Subtree shape as seen by its parent: the child offsets of the root and the
+// leftmost / rightmost x of every level, all relative to the subtree root.
+struct SvgLayout {
+    left_offset: f64,
+    right_offset: f64,
+    left_contour: Vec<f64>,
+    right_contour: Vec<f64>,
+    left: Option<Box<SvgLayout>>,
+    right: Option<Box<SvgLayout>>,
+}
+
+impl<K: Ord + Clone + Display, V: Clone> RedBlackTree<K, V> {
+    pub fn to_svg(&self, opts: &SvgOptions<K>) -> String {
+        let mut body = String::new();
+        let mut min_x = 0.0;
+        let mut max_x = 0.0;
+        let mut depth = 0;
+
+        if let Some(layout) = self.svg_layout(&self.root, opts) {
+            let layout = *layout;
+            min_x = layout.left_contour.iter().cloned().fold(0.0, f64::min);
+            max_x = layout.right_contour.iter().cloned().fold(0.0, f64::max);
+            depth = layout.left_contour.len();
+
+            let origin_x = opts.margin + opts.node_radius - min_x;
+            let origin_y = opts.margin + opts.node_radius;
+            let mut edges = String::new();
+            let mut nodes = String::new();
+            self.svg_draw(
+                &self.root,
+                &layout,
+                (origin_x, origin_y),
+                opts,
+                &mut edges,
+                &mut nodes,
+            );
+            body.push_str(&edges);
+            body.push_str(&nodes);
+        }
+
+        let width = max_x - min_x + 2.0 * (opts.margin + opts.node_radius);
+        let height = if depth == 0 {
+            2.0 * opts.margin
+        } else {
+            (depth - 1) as f64 * opts.level_gap + 2.0 * (opts.margin + opts.node_radius)
+        };
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+            w = width,
+            h = height
+        );
+        svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n");
+        svg.push_str(&body);
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn svg_layout(
+        &self,
+        node: &Option<Box<Node<K, V>>>,
+        opts: &SvgOptions<K>,
+    ) -> Option<Box<SvgLayout>> {
+        let n = node.as_ref()?;
+        let left = self.svg_layout(&n.left, opts);
+        let right = self.svg_layout(&n.right, opts);
+        let min_gap = 2.0 * opts.node_radius + opts.sibling_gap;
+
+        // Push the two subtrees apart until no level overlaps.
+        let (left_offset, right_offset) = match (&left, &right) {
+            (Some(l), Some(r)) => {
+                let mut distance = min_gap;
+                for (lr, rl) in l.right_contour.iter().zip(r.left_contour.iter()) {
+                    distance = distance.max(lr - rl + min_gap);
+                }
+                (-distance / 2.0, distance / 2.0)
+            }
+            (Some(_), None) => (-min_gap / 2.0, 0.0),
+            (None, Some(_)) => (0.0, min_gap / 2.0),
+            (None, None) => (0.0, 0.0),
+        };
+
+        let mut left_contour = vec![0.0];
+        let mut right_contour = vec![0.0];
+        let levels = left
+            .as_ref()
+            .map_or(0, |l| l.left_contour.len())
+            .max(right.as_ref().map_or(0, |r| r.left_contour.len()));
+        for level in 0..levels {
+            let from_left = left.as_ref().and_then(|l| {
+                Some((
+                    l.left_contour.get(level)? + left_offset,
+                    l.right_contour.get(level)? + left_offset,
+                ))
+            });
+            let from_right = right.as_ref().and_then(|r| {
+                Some((
+                    r.left_contour.get(level)? + right_offset,
+                    r.right_contour.get(level)? + right_offset,
+                ))
+            });
+            let (lo, hi) = match (from_left, from_right) {
+                (Some(a), Some(b)) => (a.0.min(b.0), a.1.max(b.1)),
+                (Some(a), None) | (None, Some(a)) => a,
+                (None, None) => unreachable!(),
+            };
+            left_contour.push(lo);
+            right_contour.push(hi);
+        }
+
+        Some(Box::new(SvgLayout {
+            left_offset,
+            right_offset,
+            left_contour,
+            right_contour,
+            left,
+            right,
+        }))
+    }
+
+    fn svg_draw(
+        &self,
+        node: &Option<Box<Node<K, V>>>,
+        layout: &SvgLayout,
+        (x, y): (f64, f64),
+        opts: &SvgOptions<K>,
+        edges: &mut String,
+        nodes: &mut String,
+    ) {
+        let n = match node {
+            Some(n) => n,
+            None => return,
+        };
+        let child_y = y + opts.level_gap;
+
+        if let Some(ref l) = layout.left {
+            let child_x = x + layout.left_offset;
+            self.svg_edge(
+                edges,
+                x,
+                y,
+                child_x,
+                child_y,
+                n.left.as_ref().is_some_and(|c| c.is_red()),
+            );
+            self.svg_draw(&n.left, l, (child_x, child_y), opts, edges, nodes);
+        }
+        if let Some(ref r) = layout.right {
+            let child_x = x + layout.right_offset;
+            self.svg_edge(
+                edges,
+                x,
+                y,
+                child_x,
+                child_y,
+                n.right.as_ref().is_some_and(|c| c.is_red()),
+            );
+            self.svg_draw(&n.right, r, (child_x, child_y), opts, edges, nodes);
+        }
+
+        let fill = if n.is_red() { "#d32f2f" } else { "#212121" };
+        let (stroke, stroke_width) = if opts.highlight.contains(&n.key) {
+            ("#ffb300", 4.0)
+        } else {
+            ("#000000", 1.0)
+        };
+        let _ = writeln!(
+            nodes,
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            x, y, opts.node_radius, fill, stroke, stroke_width
+        );
+        let _ = writeln!(
+            nodes,
+            "<text x=\"{}\" y=\"{}\" fill=\"white\" font-family=\"monospace\" font-size=\"12\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+            x,
+            y,
+            svg_escape(&n.key.to_string())
+        );
+        if opts.show_size {
+            let _ = writeln!(
+                nodes,
+                "<text x=\"{}\" y=\"{}\" fill=\"#616161\" font-family=\"monospace\" font-size=\"10\" text-anchor=\"start\">{}</text>",
+                x + opts.node_radius + 2.0,
+                y - opts.node_radius + 2.0,
+                n.size()
+            );
+        }
+    }
+
+    fn svg_edge(&self, out: &mut String, x1: f64, y1: f64, x2: f64, y2: f64, red: bool) {
+        let (stroke, width) = if red {
+            ("#d32f2f", 3.0)
+        } else {
+            ("#424242", 1.5)
+        };
+        let _ = writeln!(
+            out,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            x1, y1, x2, y2, stroke, width
+        );
+    }
+}
+
+fn svg_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_every_node_and_edge() {
+        let mut tree = RedBlackTree::new();
+        for key in 1..=10 {
+            tree.insert(key, ());
+        }
+        let svg = tree.to_svg(&SvgOptions {
+            highlight: vec![4, 8, 11],
+            ..SvgOptions::default()
+        });
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<circle ").count(), 10);
+        assert_eq!(svg.matches("<line ").count(), 9);
+        assert_eq!(svg.matches("stroke=\"#ffb300\"").count(), 2);
+        // Every size label is drawn, the root's holding the whole tree
+        assert!(svg.contains(">10</text>"));
+        // The red children are the ones with thick red edges
+        let red = svg.matches("fill=\"#d32f2f\"").count();
+        assert_eq!(
+            svg.matches("stroke=\"#d32f2f\" stroke-width=\"3\"").count(),
+            red
+        );
+    }
+
+    #[test]
+    fn siblings_do_not_overlap() {
+        let mut tree = RedBlackTree::new();
+        for key in 0..50 {
+            tree.insert(key, ());
+        }
+        let opts = SvgOptions {
+            show_size: false,
+            ..SvgOptions::default()
+        };
+        let svg = tree.to_svg(&opts);
+        let mut centers: Vec<(f64, f64)> = svg
+            .lines()
+            .filter(|l| l.starts_with("<circle"))
+            .map(|l| {
+                let attr = |name: &str| {
+                    let from = l.find(name).unwrap() + name.len() + 2;
+                    let to = from + l[from..].find('"').unwrap();
+                    l[from..to].parse::<f64>().unwrap()
+                };
+                (attr("cy"), attr("cx"))
+            })
+            .collect();
+        assert_eq!(centers.len(), 50);
+        centers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for pair in centers.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                assert!(pair[1].1 - pair[0].1 >= 2.0 * opts.node_radius);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_tree() {
+        let tree: RedBlackTree<i32, ()> = RedBlackTree::new();
+        let svg = tree.to_svg(&SvgOptions::default());
+        assert_eq!(svg.matches("<circle ").count(), 0);
+        assert!(svg.contains("height=\"40\""));
+    }
+}
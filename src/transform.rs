@@ -0,0 +1,268 @@
+//NOTE: ---------------------- TRANSFORMS ---------------------------------------
+//
+// Bulk edits over a range of the text, each recorded as one revision of the
+// edit history so a single undo takes it back. Line commands edit each line
+// that changes on its own, from the last line up, so only the leaves holding
+// those edits are rebuilt. Commands that rewrite a whole span (case, sorting)
+// replace only the part between the first and last char that differ.
+//
+// Line commands act on every line the range touches. A range that ends at
+// the very start of a line doesn't take that line in.
+
+use crate::column::line_text_len;
+use crate::history::{Change, EditHistory};
+use crate::rope::Rope;
+use std::ops::{Range, RangeBounds};
+
+// The lines `range` touches.
+fn line_span(rope: &Rope, range: Range<usize>) -> Range<usize> {
+    let first = rope.char_to_line(range.start);
+    let mut last = rope.char_to_line(range.end);
+    if last > first && rope.line_to_char(last) == range.end {
+        last -= 1;
+    }
+    first..last + 1
+}
+
+impl EditHistory {
+    // Puts `unit` in front of each line that has any text.
+    pub fn indent_lines<R: RangeBounds<usize>>(&mut self, range: R, unit: &str) {
+        let range = self.rope().char_range(range);
+        if unit.is_empty() {
+            return;
+        }
+        self.begin();
+        for line_idx in line_span(self.rope(), range).rev() {
+            if line_text_len(self.rope().line(line_idx)) > 0 {
+                let at = self.rope().line_to_char(line_idx);
+                self.record(Change::Insert {
+                    at,
+                    text: unit.to_string(),
+                });
+            }
+        }
+        self.commit();
+    }
+
+    // Takes one `unit` off the front of each line. A line indented some other
+    // way loses a leading tab, or up to as many leading spaces as `unit` has
+    // chars.
+    pub fn dedent_lines<R: RangeBounds<usize>>(&mut self, range: R, unit: &str) {
+        let range = self.rope().char_range(range);
+        let unit_len = unit.chars().count();
+        self.begin();
+        for line_idx in line_span(self.rope(), range).rev() {
+            let line = self.rope().line(line_idx);
+            let head: String = line.chars().take(unit_len.max(1)).collect();
+            let remove = if !unit.is_empty() && head == unit {
+                unit_len
+            } else if head.starts_with('\t') {
+                1
+            } else {
+                head.chars().take_while(|&c| c == ' ').count().min(unit_len)
+            };
+            if remove > 0 {
+                let at = self.rope().line_to_char(line_idx);
+                self.remove(at..at + remove);
+            }
+        }
+        self.commit();
+    }
+
+    pub fn trim_trailing_whitespace<R: RangeBounds<usize>>(&mut self, range: R) {
+        let range = self.rope().char_range(range);
+        self.begin();
+        for line_idx in line_span(self.rope(), range).rev() {
+            let line = self.rope().line(line_idx);
+            let end = line_text_len(line);
+            let trailing = line
+                .slice(..end)
+                .chars()
+                .rev()
+                .take_while(|c| c.is_whitespace())
+                .count();
+            if trailing > 0 {
+                let at = self.rope().line_to_char(line_idx) + end;
+                self.remove(at - trailing..at);
+            }
+        }
+        self.commit();
+    }
+
+    pub fn to_uppercase<R: RangeBounds<usize>>(&mut self, range: R) {
+        let range = self.rope().char_range(range);
+        let text = self.rope().slice(range.clone()).to_string().to_uppercase();
+        self.begin();
+        self.replace_changed(range, &text);
+        self.commit();
+    }
+
+    pub fn to_lowercase<R: RangeBounds<usize>>(&mut self, range: R) {
+        let range = self.rope().char_range(range);
+        let text = self.rope().slice(range.clone()).to_string().to_lowercase();
+        self.begin();
+        self.replace_changed(range, &text);
+        self.commit();
+    }
+
+    // Sorts the lines by their text, keeping equal lines in order.
+    pub fn sort_lines<R: RangeBounds<usize>>(&mut self, range: R) {
+        self.rewrite_lines(range, |lines| lines.sort());
+    }
+
+    // Drops each line that repeats the line before it.
+    pub fn dedup_lines<R: RangeBounds<usize>>(&mut self, range: R) {
+        self.rewrite_lines(range, |lines| lines.dedup());
+    }
+
+    // Hands `f` the text of each line, without line breaks, and puts back
+    // what it leaves. Line breaks stay where they were, except that the
+    // last line keeps the last one, which may be none at the end of the text.
+    fn rewrite_lines<R, F>(&mut self, range: R, f: F)
+    where
+        R: RangeBounds<usize>,
+        F: FnOnce(&mut Vec<String>),
+    {
+        let range = self.rope().char_range(range);
+        let span = line_span(self.rope(), range);
+        let mut lines = Vec::with_capacity(span.len());
+        let mut breaks = Vec::with_capacity(span.len());
+        for line_idx in span.clone() {
+            let line = self.rope().line(line_idx);
+            let end = line_text_len(line);
+            lines.push(line.slice(..end).to_string());
+            breaks.push(line.slice(end..).to_string());
+        }
+        f(&mut lines);
+        let mut text = String::new();
+        for (i, line) in lines.iter().enumerate() {
+            text.push_str(line);
+            let at = if i + 1 == lines.len() {
+                breaks.len() - 1
+            } else {
+                i
+            };
+            text.push_str(&breaks[at]);
+        }
+        let start = self.rope().line_to_char(span.start);
+        let end = start
+            + span
+                .map(|line_idx| self.rope().line(line_idx).len_chars())
+                .sum::<usize>();
+        self.begin();
+        self.replace_changed(start..end, &text);
+        self.commit();
+    }
+
+    // Replaces `range` with `text`, leaving alone the chars at either end
+    // that are the same in both.
+    fn replace_changed(&mut self, range: Range<usize>, text: &str) {
+        let old = self.rope().slice(range.clone());
+        let prefix = old
+            .chars()
+            .zip(text.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old
+            .chars()
+            .rev()
+            .zip(text.chars().rev())
+            .take_while(|(a, b)| a == b)
+            .count()
+            .min(old.len_chars() - prefix)
+            .min(text.chars().count() - prefix);
+        let (start, end) = (range.start + prefix, range.end - suffix);
+        let new: String = text
+            .chars()
+            .skip(prefix)
+            .take(text.chars().count() - prefix - suffix)
+            .collect();
+        if start < end {
+            self.remove(start..end);
+        }
+        if !new.is_empty() {
+            self.record(Change::Insert {
+                at: start,
+                text: new,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs `f` on a history of `text`, checks that it made at most one
+    // revision and that undo brings `text` back, and returns the result.
+    fn transformed(text: &str, f: impl FnOnce(&mut EditHistory)) -> String {
+        let mut history = EditHistory::new(Rope::from(text));
+        f(&mut history);
+        let after = history.rope().to_string();
+        if after == text {
+            assert_eq!(history.current_revision(), 0, "no-op made a revision");
+        } else {
+            assert_eq!(history.current_revision(), 1);
+            assert!(history.undo());
+            assert_eq!(history.rope().to_string(), text);
+            assert!(history.redo());
+        }
+        after
+    }
+
+    #[test]
+    fn indent_and_dedent() {
+        let text = "a\n\n  b\r\nc";
+        assert_eq!(
+            transformed(text, |h| h.indent_lines(.., "    ")),
+            "    a\n\n      b\r\n    c"
+        );
+        // Ends at the start of line 2, which is left out
+        assert_eq!(
+            transformed(text, |h| h.indent_lines(0..3, "\t")),
+            "\ta\n\n  b\r\nc"
+        );
+        assert_eq!(
+            transformed("    a\n\tb\n  c\n d", |h| h.dedent_lines(.., "    ")),
+            "a\nb\nc\nd"
+        );
+        assert_eq!(transformed("x\ny", |h| h.dedent_lines(.., "  ")), "x\ny");
+    }
+
+    #[test]
+    fn trim_keeps_line_breaks() {
+        assert_eq!(
+            transformed("a  \r\n\t\nb \t", |h| h.trim_trailing_whitespace(..)),
+            "a\r\n\nb"
+        );
+        assert_eq!(
+            transformed("a \nb \nc ", |h| h.trim_trailing_whitespace(3..4)),
+            "a \nb\nc "
+        );
+    }
+
+    #[test]
+    fn case_changes_touch_only_what_differs() {
+        assert_eq!(
+            transformed("stra\u{df}e Mixed", |h| h.to_uppercase(..)),
+            "STRASSE MIXED"
+        );
+        assert_eq!(transformed("ABC def", |h| h.to_lowercase(1..5)), "Abc def");
+        assert_eq!(transformed("abc", |h| h.to_lowercase(..)), "abc");
+    }
+
+    #[test]
+    fn sort_and_dedup_lines() {
+        assert_eq!(transformed("b\na\r\nc", |h| h.sort_lines(..)), "a\nb\r\nc");
+        assert_eq!(transformed("b\na\nc\n", |h| h.sort_lines(..)), "a\nb\nc\n");
+        assert_eq!(
+            transformed("z\nb\na\nz", |h| h.sort_lines(2..5)),
+            "z\na\nb\nz"
+        );
+        assert_eq!(
+            transformed("a\na\nb\na\na", |h| h.dedup_lines(..)),
+            "a\nb\na"
+        );
+        assert_eq!(transformed("a\nb\n", |h| h.dedup_lines(..)), "a\nb\n");
+    }
+}
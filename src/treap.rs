@@ -0,0 +1,307 @@
+//NOTE: ---------------------- TREAP ------------------------------------------
+//
+// An implicit treap: nodes are kept in sequence order, not by key, and each
+// carries a weight. Every node also holds the sum of the weights and the
+// number of nodes in its subtree, so a subtree is cut by count or by running
+// weight and glued back in O(log n) expected. Anchors weigh each one by its
+// distance from the previous anchor; soft wrap weighs each line by its rows.
+//
+// Nodes sit in an arena and are named by slot, with parent links so a slot
+// can find its own rank and running weight. A subtree's weights can be
+// zeroed in O(1) by tagging its root; the tag is pushed down lazily.
+
+pub(crate) const NIL: usize = usize::MAX;
+
+#[derive(Debug, Clone)]
+pub(crate) struct TreapNode<T> {
+    pub(crate) value: T,
+    pub(crate) weight: usize,
+    sum: usize,  // Weights in this subtree
+    size: usize, // Nodes in this subtree
+    priority: u64,
+    zeroed: bool, // The children's weights are all waiting to be set to zero
+    pub(crate) left: usize,
+    pub(crate) right: usize,
+    pub(crate) parent: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Treap<T> {
+    nodes: Vec<TreapNode<T>>,
+    free: Vec<usize>,
+    seed: u64,
+}
+
+impl<T> Default for Treap<T> {
+    fn default() -> Self {
+        Treap {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            seed: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+}
+
+impl<T> Treap<T> {
+    pub(crate) fn node(&self, t: usize) -> &TreapNode<T> {
+        &self.nodes[t]
+    }
+
+    pub(crate) fn get(&self, t: usize) -> Option<&TreapNode<T>> {
+        self.nodes.get(t)
+    }
+
+    pub(crate) fn value_mut(&mut self, t: usize) -> &mut T {
+        &mut self.nodes[t].value
+    }
+
+    // Slots holding a node, whether or not it is still in a tree.
+    pub(crate) fn live(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    pub(crate) fn slots(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+    }
+
+    pub(crate) fn sum(&self, t: usize) -> usize {
+        if t == NIL {
+            0
+        } else {
+            self.nodes[t].sum
+        }
+    }
+
+    pub(crate) fn size(&self, t: usize) -> usize {
+        if t == NIL {
+            0
+        } else {
+            self.nodes[t].size
+        }
+    }
+
+    fn update(&mut self, t: usize) {
+        let (left, right) = (self.nodes[t].left, self.nodes[t].right);
+        self.nodes[t].sum = self.nodes[t].weight + self.sum(left) + self.sum(right);
+        self.nodes[t].size = 1 + self.size(left) + self.size(right);
+        for child in [left, right] {
+            if child != NIL {
+                self.nodes[child].parent = t;
+            }
+        }
+    }
+
+    // Sets every weight in the subtree to zero.
+    pub(crate) fn zero(&mut self, t: usize) {
+        if t != NIL {
+            let node = &mut self.nodes[t];
+            node.weight = 0;
+            node.sum = 0;
+            node.zeroed = true;
+        }
+    }
+
+    fn push(&mut self, t: usize) {
+        if self.nodes[t].zeroed {
+            self.nodes[t].zeroed = false;
+            self.zero(self.nodes[t].left);
+            self.zero(self.nodes[t].right);
+        }
+    }
+
+    // Makes `t` the root of a tree of its own.
+    pub(crate) fn detach(&mut self, t: usize) -> usize {
+        if t != NIL {
+            self.nodes[t].parent = NIL;
+        }
+        t
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Nodes
+    //   ***************************************************************************
+    // A one-node tree. The slot is reused from freed nodes when there is one.
+    pub(crate) fn alloc(&mut self, weight: usize, value: T) -> usize {
+        // splitmix64, so the shape doesn't depend on insertion order
+        self.seed = self.seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut priority = self.seed;
+        priority = (priority ^ (priority >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        priority = (priority ^ (priority >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        priority ^= priority >> 31;
+        let node = TreapNode {
+            value,
+            weight,
+            sum: weight,
+            size: 1,
+            priority,
+            zeroed: false,
+            left: NIL,
+            right: NIL,
+            parent: NIL,
+        };
+        match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    // Frees one node that is no longer in any tree. Its value stays readable
+    // until the slot is reused.
+    pub(crate) fn free(&mut self, t: usize) {
+        self.free.push(t);
+    }
+
+    // Frees a whole subtree.
+    pub(crate) fn release(&mut self, t: usize) {
+        if t != NIL {
+            self.release(self.nodes[t].left);
+            self.release(self.nodes[t].right);
+            self.free.push(t);
+        }
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Split and merge
+    //   ***************************************************************************
+    // Splits off the first `k` nodes.
+    pub(crate) fn split_rank(&mut self, t: usize, k: usize) -> (usize, usize) {
+        if t == NIL {
+            return (NIL, NIL);
+        }
+        self.push(t);
+        let (left, right) = (self.nodes[t].left, self.nodes[t].right);
+        if self.size(left) < k {
+            let (l, r) = self.split_rank(right, k - self.size(left) - 1);
+            self.nodes[t].right = l;
+            self.update(t);
+            (t, r)
+        } else {
+            let (l, r) = self.split_rank(left, k);
+            self.nodes[t].left = r;
+            self.update(t);
+            (l, t)
+        }
+    }
+
+    // Splits off the nodes whose running weight, counted through the node
+    // itself and starting from `base`, is below `pos` (or at most `pos` when
+    // `inclusive`).
+    pub(crate) fn split_sum(
+        &mut self,
+        t: usize,
+        pos: usize,
+        inclusive: bool,
+        base: usize,
+    ) -> (usize, usize) {
+        if t == NIL {
+            return (NIL, NIL);
+        }
+        self.push(t);
+        let (left, right) = (self.nodes[t].left, self.nodes[t].right);
+        let at = base + self.sum(left) + self.nodes[t].weight;
+        if at < pos || (inclusive && at == pos) {
+            let (l, r) = self.split_sum(right, pos, inclusive, at);
+            self.nodes[t].right = l;
+            self.update(t);
+            (t, r)
+        } else {
+            let (l, r) = self.split_sum(left, pos, inclusive, base);
+            self.nodes[t].left = r;
+            self.update(t);
+            (l, t)
+        }
+    }
+
+    pub(crate) fn merge(&mut self, a: usize, b: usize) -> usize {
+        if a == NIL {
+            return b;
+        }
+        if b == NIL {
+            return a;
+        }
+        if self.nodes[a].priority > self.nodes[b].priority {
+            self.push(a);
+            let right = self.nodes[a].right;
+            self.nodes[a].right = self.merge(right, b);
+            self.update(a);
+            a
+        } else {
+            self.push(b);
+            let left = self.nodes[b].left;
+            self.nodes[b].left = self.merge(a, left);
+            self.update(b);
+            b
+        }
+    }
+
+    // Rewrites the weight of the first node in `t`.
+    pub(crate) fn map_first_weight(&mut self, t: usize, f: impl FnOnce(usize) -> usize) {
+        if t == NIL {
+            return;
+        }
+        self.push(t);
+        let left = self.nodes[t].left;
+        if left == NIL {
+            self.nodes[t].weight = f(self.nodes[t].weight);
+        } else {
+            self.map_first_weight(left, f);
+        }
+        self.update(t);
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Finding a node's place
+    //   ***************************************************************************
+    fn path_from_root(&self, t: usize) -> Vec<usize> {
+        let mut path = vec![t];
+        while self.nodes[*path.last().unwrap()].parent != NIL {
+            path.push(self.nodes[*path.last().unwrap()].parent);
+        }
+        path.reverse();
+        path
+    }
+
+    // The running weight through `t`. Read-only, so zero tags on the way
+    // down are applied in passing instead of being pushed.
+    pub(crate) fn position(&self, t: usize) -> usize {
+        let path = self.path_from_root(t);
+        let mut pos = 0;
+        let mut zeroed = false;
+        for (i, &t) in path.iter().enumerate() {
+            let node = &self.nodes[t];
+            let through = path.get(i + 1).is_none_or(|&next| next == node.right);
+            if through && !zeroed {
+                pos += node.weight;
+                if !node.zeroed {
+                    pos += self.sum(node.left);
+                }
+            }
+            zeroed |= node.zeroed;
+        }
+        pos
+    }
+
+    // How many nodes come before `t` in its tree.
+    pub(crate) fn rank(&self, t: usize) -> usize {
+        let path = self.path_from_root(t);
+        let mut rank = 0;
+        for (i, &t) in path.iter().enumerate() {
+            let node = &self.nodes[t];
+            if path.get(i + 1).is_none_or(|&next| next == node.right) {
+                rank += self.size(node.left) + (i + 1 < path.len()) as usize;
+            }
+        }
+        rank
+    }
+}
@@ -0,0 +1,280 @@
+//NOTE: ---------------------- WORDS AND PARAGRAPHS -----------------------------
+//
+// Cursor motions by word and by paragraph. Words are runs of chars of one
+// class; the classes come from `char` methods, plus a list of extra chars
+// that count as word chars (`_` by default, for identifiers). Scans go
+// through the char iterators, which cross leaf boundaries without copying,
+// so a motion only touches the chars it walks over. A paragraph is a run of
+// lines that aren't blank; only whitespace makes a line blank.
+
+use crate::rope::Rope;
+use crate::slice::RopeSlice;
+use std::ops::Range;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CharClass {
+    Whitespace, // Line breaks included
+    Word,
+    Punctuation, // Anything else
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordClasses {
+    pub word_chars: Vec<char>, // Word chars besides the alphanumerics
+}
+
+impl Default for WordClasses {
+    fn default() -> Self {
+        WordClasses {
+            word_chars: vec!['_'],
+        }
+    }
+}
+
+impl WordClasses {
+    pub fn class_of(&self, c: char) -> CharClass {
+        if c.is_whitespace() {
+            CharClass::Whitespace
+        } else if c.is_alphanumeric() || self.word_chars.contains(&c) {
+            CharClass::Word
+        } else {
+            CharClass::Punctuation
+        }
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Words
+//   ***************************************************************************
+impl<'a> RopeSlice<'a> {
+    pub fn next_word_start(&self, char_idx: usize) -> usize {
+        self.next_word_start_with(char_idx, &WordClasses::default())
+    }
+
+    pub fn prev_word_start(&self, char_idx: usize) -> usize {
+        self.prev_word_start_with(char_idx, &WordClasses::default())
+    }
+
+    pub fn word_at(&self, char_idx: usize) -> Option<Range<usize>> {
+        self.word_at_with(char_idx, &WordClasses::default())
+    }
+
+    // Past the rest of the word under `char_idx`, then past the whitespace
+    // after it. Stops at the end of the slice.
+    pub fn next_word_start_with(&self, char_idx: usize, classes: &WordClasses) -> usize {
+        let mut chars = self.chars_at(char_idx).peekable();
+        let mut pos = char_idx;
+        if let Some(first) = chars.peek().map(|&c| classes.class_of(c)) {
+            while chars.next_if(|&c| classes.class_of(c) == first).is_some() {
+                pos += 1;
+            }
+            while chars
+                .next_if(|&c| classes.class_of(c) == CharClass::Whitespace)
+                .is_some()
+            {
+                pos += 1;
+            }
+        }
+        pos
+    }
+
+    // Back over the whitespace before `char_idx`, then to the start of the
+    // word before that. Stops at the start of the slice.
+    pub fn prev_word_start_with(&self, char_idx: usize, classes: &WordClasses) -> usize {
+        let mut chars = self.slice(..char_idx).chars().rev().peekable();
+        let mut pos = char_idx;
+        while chars
+            .next_if(|&c| classes.class_of(c) == CharClass::Whitespace)
+            .is_some()
+        {
+            pos -= 1;
+        }
+        if let Some(last) = chars.peek().map(|&c| classes.class_of(c)) {
+            while chars.next_if(|&c| classes.class_of(c) == last).is_some() {
+                pos -= 1;
+            }
+        }
+        pos
+    }
+
+    // The word or run of punctuation under `char_idx`, or just before it when
+    // the cursor sits at its end. None when there is only whitespace.
+    pub fn word_at_with(&self, char_idx: usize, classes: &WordClasses) -> Option<Range<usize>> {
+        let class_at = |idx: usize| self.chars_at(idx).next().map(|c| classes.class_of(c));
+        let at = [Some(char_idx), char_idx.checked_sub(1)]
+            .into_iter()
+            .flatten()
+            .find(|&idx| class_at(idx).is_some_and(|class| class != CharClass::Whitespace))?;
+        let class = class_at(at)?;
+        let before = self
+            .slice(..at)
+            .chars()
+            .rev()
+            .take_while(|&c| classes.class_of(c) == class)
+            .count();
+        let after = self
+            .chars_at(at)
+            .take_while(|&c| classes.class_of(c) == class)
+            .count();
+        Some(at - before..at + after)
+    }
+}
+
+//NOTE:***************************************************************************
+//   *  Paragraphs
+//   ***************************************************************************
+impl<'a> RopeSlice<'a> {
+    fn is_blank_line(&self, line_idx: usize) -> bool {
+        self.line(line_idx).chars().all(char::is_whitespace)
+    }
+
+    // The first line of the paragraph holding `line_idx`.
+    fn paragraph_first_line(&self, mut line_idx: usize) -> usize {
+        while line_idx > 0 && !self.is_blank_line(line_idx - 1) {
+            line_idx -= 1;
+        }
+        line_idx
+    }
+
+    // The start of the next paragraph after the one under `char_idx`, or the
+    // end of the slice if there is none.
+    pub fn next_paragraph(&self, char_idx: usize) -> usize {
+        let lines = self.len_lines();
+        let mut line = self.char_to_line(char_idx);
+        while line < lines && !self.is_blank_line(line) {
+            line += 1;
+        }
+        while line < lines && self.is_blank_line(line) {
+            line += 1;
+        }
+        if line < lines {
+            self.line_to_char(line)
+        } else {
+            self.len_chars()
+        }
+    }
+
+    // The start of the paragraph under `char_idx` if that is before it, else
+    // the start of the paragraph before; 0 if there is none.
+    pub fn prev_paragraph(&self, char_idx: usize) -> usize {
+        let mut line = self.char_to_line(char_idx);
+        if !self.is_blank_line(line) {
+            line = self.paragraph_first_line(line);
+            if self.line_to_char(line) < char_idx {
+                return self.line_to_char(line);
+            }
+        }
+        while line > 0 && self.is_blank_line(line - 1) {
+            line -= 1;
+        }
+        if line == 0 {
+            return 0;
+        }
+        self.line_to_char(self.paragraph_first_line(line - 1))
+    }
+}
+
+impl Rope {
+    pub fn next_word_start(&self, char_idx: usize) -> usize {
+        self.slice(..).next_word_start(char_idx)
+    }
+
+    pub fn prev_word_start(&self, char_idx: usize) -> usize {
+        self.slice(..).prev_word_start(char_idx)
+    }
+
+    pub fn word_at(&self, char_idx: usize) -> Option<Range<usize>> {
+        self.slice(..).word_at(char_idx)
+    }
+
+    pub fn next_word_start_with(&self, char_idx: usize, classes: &WordClasses) -> usize {
+        self.slice(..).next_word_start_with(char_idx, classes)
+    }
+
+    pub fn prev_word_start_with(&self, char_idx: usize, classes: &WordClasses) -> usize {
+        self.slice(..).prev_word_start_with(char_idx, classes)
+    }
+
+    pub fn word_at_with(&self, char_idx: usize, classes: &WordClasses) -> Option<Range<usize>> {
+        self.slice(..).word_at_with(char_idx, classes)
+    }
+
+    pub fn next_paragraph(&self, char_idx: usize) -> usize {
+        self.slice(..).next_paragraph(char_idx)
+    }
+
+    pub fn prev_paragraph(&self, char_idx: usize) -> usize {
+        self.slice(..).prev_paragraph(char_idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rope::tests::from_chunks;
+
+    #[test]
+    fn word_motions() {
+        let rope = Rope::from("foo_bar.baz  qux\n");
+        let forward: Vec<usize> = [0, 7, 8, 13, 17]
+            .iter()
+            .map(|&i| rope.next_word_start(i))
+            .collect();
+        assert_eq!(forward, [7, 8, 13, 17, 17]);
+        let back: Vec<usize> = [17, 13, 8, 7, 0]
+            .iter()
+            .map(|&i| rope.prev_word_start(i))
+            .collect();
+        assert_eq!(back, [13, 8, 7, 0, 0]);
+    }
+
+    #[test]
+    fn word_under_the_cursor() {
+        let rope = Rope::from("foo_bar.baz  qux");
+        assert_eq!(rope.word_at(5), Some(0..7));
+        assert_eq!(rope.word_at(7), Some(7..8));
+        assert_eq!(rope.word_at(11), Some(8..11)); // Just after the word
+        assert_eq!(rope.word_at(12), None);
+        assert_eq!(rope.word_at(16), Some(13..16));
+        assert_eq!(Rope::new().word_at(0), None);
+    }
+
+    #[test]
+    fn word_chars_are_configurable() {
+        let rope = Rope::from("foo-bar_x");
+        let dashes = WordClasses {
+            word_chars: vec!['-'],
+        };
+        assert_eq!(dashes.class_of('_'), CharClass::Punctuation);
+        assert_eq!(rope.word_at_with(1, &dashes), Some(0..7));
+        assert_eq!(rope.next_word_start_with(0, &dashes), 7);
+        assert_eq!(rope.word_at(1), Some(0..3));
+        assert_eq!(rope.next_word_start(4), 9);
+    }
+
+    #[test]
+    fn motions_cross_leaves_and_stay_in_slices() {
+        let rope = from_chunks(&["he", "llo w", "\u{f6}rld", "!"]);
+        assert_eq!(rope.next_word_start(0), 6);
+        assert_eq!(rope.prev_word_start(11), 6);
+        assert_eq!(rope.word_at(8), Some(6..11));
+        let slice = rope.slice(2..9); // "llo w\u{f6}r"
+        assert_eq!(slice.next_word_start(0), 4);
+        assert_eq!(slice.next_word_start(4), 7);
+        assert_eq!(slice.prev_word_start(4), 0);
+        assert_eq!(slice.word_at(5), Some(4..7));
+    }
+
+    #[test]
+    fn paragraph_motions() {
+        let rope = Rope::from("a\nb\n\n \nc\nd\n\ne");
+        assert_eq!(rope.next_paragraph(0), 7);
+        assert_eq!(rope.next_paragraph(7), 12);
+        assert_eq!(rope.next_paragraph(12), 13);
+        assert_eq!(rope.prev_paragraph(13), 12);
+        assert_eq!(rope.prev_paragraph(12), 7);
+        assert_eq!(rope.prev_paragraph(7), 0);
+        assert_eq!(rope.prev_paragraph(3), 0);
+        assert_eq!(rope.prev_paragraph(0), 0);
+    }
+}
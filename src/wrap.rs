@@ -0,0 +1,309 @@
+//NOTE: ---------------------- SOFT WRAP ----------------------------------------
+//
+// Visual rows for a rope wrapped to a width. Each buffer line is one node of
+// a treap, in line order, holding how many rows it wraps to; every node also
+// sums the rows of its subtree, so the first row of a line and the line at a
+// row are both found in O(log n). Where a line's rows break is not stored:
+// it is worked out again from the line's text when a position is mapped.
+// After an edit only the lines it touched are wrapped again, and their nodes
+// are swapped in with two splits and two merges. The treap is the crate's
+// arena treap, with a line's rows as its weight.
+//
+// A row breaks after the last space or tab that fits, or mid-word when there
+// is none. Whitespace may hang past the width instead of starting a row.
+// Chars are measured as `char_to_column` measures them.
+
+use crate::column::{char_columns, line_text_len};
+use crate::rope::Rope;
+use crate::slice::RopeSlice;
+use crate::treap::{Treap, NIL};
+use std::ops::Range;
+
+#[derive(Debug, Clone)]
+pub struct WrapMap {
+    wrap_width: usize, // 0 turns wrapping off
+    tab_width: usize,
+    lines: Treap<()>, // One node per line, weighing its visual rows
+    root: usize,
+}
+
+// Char offsets, within `line`, where each of its rows starts. The line break
+// takes no room.
+fn row_starts(line: RopeSlice<'_>, wrap_width: usize, tab_width: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    if wrap_width == 0 {
+        return starts;
+    }
+    let mut row: Vec<char> = Vec::new(); // Chars since the row started
+    let mut row_start = 0;
+    let mut col = 0;
+    let mut break_at = 0; // Just past the last whitespace in the row
+    for (i, c) in line.chars().enumerate() {
+        if c == '\n' || c == '\r' {
+            break;
+        }
+        let width = char_columns(c, col, tab_width);
+        let hangs = c == ' ' || c == '\t';
+        if !hangs && col > 0 && col + width > wrap_width {
+            let start = if break_at > row_start { break_at } else { i };
+            starts.push(start);
+            row.drain(..start - row_start);
+            row_start = start;
+            col = row
+                .iter()
+                .fold(0, |col, &c| col + char_columns(c, col, tab_width));
+        }
+        row.push(c);
+        col += char_columns(c, col, tab_width);
+        if hangs {
+            break_at = i + 1;
+        }
+    }
+    starts
+}
+
+impl WrapMap {
+    pub fn new(rope: &Rope, wrap_width: usize, tab_width: usize) -> Self {
+        let mut map = WrapMap {
+            wrap_width,
+            tab_width,
+            lines: Treap::default(),
+            root: NIL,
+        };
+        map.rewrap_all(rope);
+        map
+    }
+
+    pub fn wrap_width(&self) -> usize {
+        self.wrap_width
+    }
+
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    pub fn set_wrap_width(&mut self, rope: &Rope, wrap_width: usize) {
+        self.wrap_width = wrap_width;
+        self.rewrap_all(rope);
+    }
+
+    pub fn set_tab_width(&mut self, rope: &Rope, tab_width: usize) {
+        self.tab_width = tab_width;
+        self.rewrap_all(rope);
+    }
+
+    fn rewrap_all(&mut self, rope: &Rope) {
+        self.lines.clear();
+        self.root = self.build(rope, 0..rope.len_lines());
+    }
+
+    fn rows_of(&self, rope: &Rope, line_idx: usize) -> usize {
+        row_starts(rope.line(line_idx), self.wrap_width, self.tab_width).len()
+    }
+
+    // A tree of the given lines, freshly wrapped.
+    fn build(&mut self, rope: &Rope, lines: Range<usize>) -> usize {
+        let mut t = NIL;
+        for line_idx in lines {
+            let node = self.lines.alloc(self.rows_of(rope, line_idx), ());
+            t = self.lines.merge(t, node);
+        }
+        t
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Following edits
+    //   ***************************************************************************
+    // Call after each edit of `rope`, with the char range the inserted text
+    // now covers; a removal is the empty range where it happened. Only the
+    // lines that range touches are wrapped again.
+    pub fn edited(&mut self, rope: &Rope, changed: Range<usize>) {
+        // From the char before, in case the edit split a CRLF there
+        let first = rope.char_to_line(changed.start.saturating_sub(1));
+        let last = rope.char_to_line(changed.end);
+        let added = rope.len_lines() as isize - self.lines.size(self.root) as isize;
+        let old_last = (last as isize - added).max(first as isize) as usize;
+        let (before, rest) = self.lines.split_rank(self.root, first);
+        let (old, after) = self.lines.split_rank(rest, old_last + 1 - first);
+        self.lines.release(old);
+        let new = self.build(rope, first..last + 1);
+        let root = self.lines.merge(before, new);
+        self.root = self.lines.merge(root, after);
+    }
+
+    //NOTE:***************************************************************************
+    //   *  Rows and positions
+    //   ***************************************************************************
+    pub fn visual_rows(&self) -> usize {
+        self.lines.sum(self.root)
+    }
+
+    // The first visual row of a buffer line.
+    pub fn line_to_row(&self, line_idx: usize) -> usize {
+        let mut t = self.root;
+        let mut k = line_idx;
+        let mut rows = 0;
+        while t != NIL {
+            let node = self.lines.node(t);
+            let left = self.lines.size(node.left);
+            if k < left {
+                t = node.left;
+            } else if k == left {
+                return rows + self.lines.sum(node.left);
+            } else {
+                rows += self.lines.sum(node.left) + node.weight;
+                k -= left + 1;
+                t = node.right;
+            }
+        }
+        rows
+    }
+
+    // The buffer line that `row` belongs to, and which of that line's rows it
+    // is. Rows past the end map to the last row of the last line.
+    pub fn row_to_line(&self, row: usize) -> (usize, usize) {
+        let row = row.min(self.visual_rows().saturating_sub(1));
+        let mut t = self.root;
+        let mut r = row;
+        let mut line = 0;
+        while t != NIL {
+            let node = self.lines.node(t);
+            let left = self.lines.sum(node.left);
+            if r < left {
+                t = node.left;
+            } else if r < left + node.weight {
+                return (line + self.lines.size(node.left), r - left);
+            } else {
+                r -= left + node.weight;
+                line += self.lines.size(node.left) + 1;
+                t = node.right;
+            }
+        }
+        (line, 0)
+    }
+
+    // The visual (row, column) of a char index.
+    pub fn char_to_visual(&self, rope: &Rope, char_idx: usize) -> (usize, usize) {
+        let line_idx = rope.char_to_line(char_idx);
+        let line = rope.line(line_idx);
+        let offset = char_idx - rope.line_to_char(line_idx);
+        let starts = row_starts(line, self.wrap_width, self.tab_width);
+        let row = starts.partition_point(|&start| start <= offset) - 1;
+        let col = line
+            .slice(starts[row]..offset)
+            .chars()
+            .fold(0, |col, c| col + char_columns(c, col, self.tab_width));
+        (self.line_to_row(line_idx) + row, col)
+    }
+
+    // The char index shown at a visual (row, column). A column inside a tab
+    // maps to the tab; one past the end of the row maps to the row's last
+    // char, or to the end of the line's text on its last row.
+    pub fn visual_to_char(&self, rope: &Rope, row: usize, col: usize) -> usize {
+        let (line_idx, row) = self.row_to_line(row);
+        let line = rope.line(line_idx);
+        let starts = row_starts(line, self.wrap_width, self.tab_width);
+        let start = starts[row];
+        let end = match starts.get(row + 1) {
+            Some(&next) => next - 1,
+            None => line_text_len(line),
+        };
+        let mut at = start;
+        let mut x = 0;
+        for c in line.slice(start..end).chars() {
+            let width = char_columns(c, x, self.tab_width);
+            if x + width > col {
+                break;
+            }
+            x += width;
+            at += 1;
+        }
+        rope.line_to_char(line_idx) + at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Rng;
+
+    fn starts(text: &str, wrap_width: usize) -> Vec<usize> {
+        row_starts(Rope::from(text).line(0), wrap_width, 4)
+    }
+
+    // Every line starts on the same row as in a map built from scratch.
+    fn assert_same_rows(map: &WrapMap, rope: &Rope) {
+        let full = WrapMap::new(rope, map.wrap_width(), map.tab_width());
+        assert_eq!(map.visual_rows(), full.visual_rows());
+        for line in 0..rope.len_lines() {
+            assert_eq!(
+                map.line_to_row(line),
+                full.line_to_row(line),
+                "line {}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn rows_break_after_whitespace() {
+        assert_eq!(starts("hello world foo", 8), [0, 6, 12]);
+        assert_eq!(starts("abcdefghij", 4), [0, 4, 8]);
+        assert_eq!(starts("ab      cd", 4), [0, 8]); // Spaces hang past the edge
+        assert_eq!(starts("\u{65e5}\u{672c}\u{8a9e}", 4), [0, 2]);
+        assert_eq!(starts("a\tb\tc", 6), [0, 4]);
+        assert_eq!(starts("abcdefghij\r\n", 10), [0]);
+        assert_eq!(starts("abcdefghij", 0), [0]);
+    }
+
+    #[test]
+    fn incremental_rewrap_matches_a_full_one() {
+        let mut rng = Rng(0xd807_aa98_a303_0242);
+        let pieces = ["word ", "x", "\t", "\n", "\r\n", "\u{65e5}", "longerword"];
+        let mut rope = Rope::from("start\n");
+        let mut map = WrapMap::new(&rope, 12, 4);
+        for step in 0..1500 {
+            let len = rope.len_chars();
+            if len > 0 && rng.below(3) == 0 {
+                let start = rng.below(len);
+                let end = start + rng.below((len - start).min(40) + 1);
+                rope.remove(start..end);
+                map.edited(&rope, start..start);
+            } else {
+                let at = rng.below(len + 1);
+                let text: String = (0..1 + rng.below(6))
+                    .map(|_| pieces[rng.below(pieces.len())])
+                    .collect();
+                rope.insert(at, &text);
+                map.edited(&rope, at..at + text.chars().count());
+            }
+            if step % 10 == 0 {
+                assert_same_rows(&map, &rope);
+            }
+        }
+        assert_same_rows(&map, &rope);
+        map.set_wrap_width(&rope, 5);
+        assert_same_rows(&map, &rope);
+    }
+
+    #[test]
+    fn visual_positions_round_trip() {
+        let rope = Rope::from("one two three four\n\u{65e5}\u{672c}\u{8a9e}\u{65e5}\u{672c}\nx\n");
+        let map = WrapMap::new(&rope, 6, 4);
+        assert_eq!(map.visual_rows(), 8);
+        assert_eq!(map.row_to_line(4), (1, 0));
+        assert_eq!(map.row_to_line(99), (3, 0));
+        assert_eq!(map.line_to_row(2), 6);
+        assert_eq!(map.char_to_visual(&rope, 8), (2, 0)); // "three"
+        assert_eq!(map.char_to_visual(&rope, 21), (4, 4));
+        for c in 0..rope.len_chars() {
+            if rope.char_at(c).is_some_and(|c| !c.is_whitespace()) {
+                let (row, col) = map.char_to_visual(&rope, c);
+                assert_eq!(map.visual_to_char(&rope, row, col), c, "char {}", c);
+            }
+        }
+        // Past the end of a wrapped row stays on that row
+        assert_eq!(map.visual_to_char(&rope, 0, 50), 3);
+        assert_eq!(map.visual_to_char(&rope, 3, 50), 18);
+    }
+}